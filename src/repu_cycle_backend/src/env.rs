@@ -0,0 +1,68 @@
+// System API of the canister. Natively, as under `cargo test`, there is no IC
+// to ask, so the time, caller and cycles balance come from values the tests
+// control instead.
+
+#[cfg(not(test))]
+use candid::Principal;
+
+#[cfg(not(test))]
+pub fn time() -> u64 {
+    ic_cdk::api::time()
+}
+
+#[cfg(not(test))]
+pub fn caller() -> Principal {
+    ic_cdk::caller()
+}
+
+#[cfg(not(test))]
+pub fn id() -> Principal {
+    ic_cdk::id()
+}
+
+#[cfg(not(test))]
+pub fn canister_balance() -> u64 {
+    ic_cdk::api::canister_balance()
+}
+
+#[cfg(test)]
+pub use mock::*;
+
+#[cfg(test)]
+mod mock {
+    use candid::Principal;
+    use std::cell::Cell;
+
+    // Tests start from a fixed, non-zero time so day arithmetic is meaningful
+    const START_NANOS: u64 = 1_700_000_000 * 1_000_000_000;
+
+    thread_local! {
+        static TIME: Cell<u64> = const { Cell::new(START_NANOS) };
+        static CALLER: Cell<Principal> = const { Cell::new(Principal::anonymous()) };
+        static BALANCE: Cell<u64> = const { Cell::new(u64::MAX) };
+    }
+
+    pub fn time() -> u64 {
+        TIME.with(Cell::get)
+    }
+
+    pub fn advance_secs(secs: u64) {
+        TIME.with(|time| time.set(time.get() + secs * 1_000_000_000));
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(Cell::get)
+    }
+
+    pub fn set_caller(principal: Principal) {
+        CALLER.with(|caller| caller.set(principal));
+    }
+
+    pub fn id() -> Principal {
+        Principal::from_slice(&[0xCA, 0xFE])
+    }
+
+    pub fn canister_balance() -> u64 {
+        BALANCE.with(Cell::get)
+    }
+}
@@ -0,0 +1,190 @@
+// Storage layout of the first release: ten collections written by
+// ic_cdk::storage::stable_save as one candid argument list at the start of
+// stable memory. Only read once, by the first upgrade off that layout.
+
+use super::{env, generate_id, Footprints, State};
+use candid::de::IDLDeserialize;
+use candid::utils::ArgumentDecoder;
+use candid::CandidType;
+use ic_stable_structures::Memory as _;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::stable_map::stable_memory;
+
+const WASM_PAGE_SIZE: u64 = 65_536;
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct User {
+    pub id: String,
+    pub full_name: String,
+    pub email: String,
+    pub hashed_password: String,
+    pub tokens: u32,
+    pub role: Role,
+    pub preferred_language: String,
+    pub session_token: Option<SessionToken>,
+    pub achievements: Vec<String>,
+    pub challenges_completed: Vec<String>,
+    pub completed_courses: Vec<String>,
+    pub passed_quizzes: Vec<String>,
+    pub notifications: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) enum Role {
+    Admin,
+    User,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct UserFootprint {
+    pub waste_generated: u32,
+    pub recyclable_waste: u32,
+    pub footprint_score: f32,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Quiz {
+    pub level: u32,
+    pub questions: Vec<String>,
+    pub options: Vec<Vec<String>>,
+    pub correct_answers: Vec<String>,
+    pub reward: u32,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Challenge {
+    pub description: String,
+    pub reward_tokens: u32,
+    pub required_courses: Vec<String>,
+    pub required_quizzes: Vec<String>,
+    pub participants: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Token {
+    pub owner: String,
+    pub balance: u32,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct SessionToken {
+    pub token: String,
+    pub expires_at: i64,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Course {
+    pub title: String,
+    pub levels: HashMap<u32, Quiz>,
+    pub educational_resources: Vec<String>,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Notification {
+    pub user_id: String,
+    pub message: String,
+    pub timestamp: i64,
+    pub notification_type: NotificationType,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) enum NotificationType {
+    CourseAdded,
+    ChallengeAdded,
+    ChallengeStarted,
+    ChallengeParticipated,
+    ChallengeReminder,
+    AchievementShared,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Feedback {
+    pub user_id: String,
+    pub feedback: String,
+}
+
+#[derive(CandidType, Deserialize, Serialize)]
+pub(crate) struct Reward {
+    pub id: String,
+    pub description: String,
+    pub cost_tokens: u32,
+}
+
+pub(crate) type Snapshot = (
+    HashMap<String, User>,
+    HashMap<String, UserFootprint>,
+    HashMap<String, Quiz>,
+    HashMap<String, Challenge>,
+    HashMap<String, Token>,
+    Vec<String>,
+    HashMap<String, Course>,
+    HashMap<String, Vec<Notification>>,
+    Vec<Feedback>,
+    HashMap<String, Reward>,
+);
+
+// The baseline collections, if stable memory still holds them. Must run
+// before anything touches the memory manager, which claims the same bytes.
+pub(crate) fn read_snapshot() -> Option<Snapshot> {
+    let memory = stable_memory();
+    if memory.size() == 0 {
+        return None;
+    }
+    let mut magic = [0u8; 4];
+    memory.read(0, &mut magic);
+    if &magic != b"DIDL" {
+        return None;
+    }
+
+    let mut bytes = vec![0; (memory.size() * WASM_PAGE_SIZE) as usize];
+    memory.read(0, &mut bytes);
+    // The argument list is followed by the rest of the last page, so the
+    // trailing bytes are not checked
+    let mut de = IDLDeserialize::new(&bytes).expect("failed to read baseline storage header");
+    Some(Snapshot::decode(&mut de).expect("failed to decode baseline storage"))
+}
+
+// Carry the baseline collections over. Fields added since take their serde
+// defaults, the same as a record saved by any older release. Sessions are
+// dropped, and since baseline password hashes used a salt that was never
+// stored, migrated users sign in again after a password reset.
+pub(crate) fn into_state(snapshot: Snapshot) -> State {
+    let (users, footprints, quizzes, challenges, tokens, log, courses, notifications, feedbacks, rewards) = snapshot;
+
+    let mut state = State::default();
+    for (id, user) in users {
+        state.users.insert(id, upgrade(&user));
+    }
+    let recorded_at = (env::time() / 1_000_000_000) as i64;
+    state.footprints = footprints.into_iter()
+        .map(|(id, footprint)| (id, vec![(recorded_at, upgrade(&footprint))]))
+        .collect::<Footprints>();
+    state.quizzes = upgrade(&quizzes);
+    state.challenges = upgrade(&challenges);
+    state.tokens = upgrade(&tokens);
+    state.log = log;
+    for (title, course) in courses {
+        state.courses.insert(title, upgrade(&course));
+    }
+    for (id, list) in notifications {
+        state.notifications.insert(id, upgrade(&list));
+    }
+    state.feedbacks = feedbacks.iter()
+        .map(|feedback| {
+            let mut feedback: super::Feedback = upgrade(feedback);
+            feedback.id = generate_id();
+            feedback
+        })
+        .collect();
+    state.rewards = upgrade(&rewards);
+    state
+}
+
+// Re-read a baseline value as its current type through CBOR
+fn upgrade<T: Serialize, U: DeserializeOwned>(value: &T) -> U {
+    let bytes = serde_cbor::to_vec(value).expect("failed to encode baseline value");
+    serde_cbor::from_slice(&bytes).expect("failed to convert baseline value")
+}
@@ -1,14 +1,28 @@
 use ic_cdk_macros::*;
-use ic_cdk::storage;
+use std::cell::RefCell;
 use std::collections::HashMap;
-use ic_cdk::export::candid::{CandidType, Deserialize};
+use std::time::Duration;
+use candid::{CandidType, Deserialize, Principal};
+use serde::Serialize;
+use ic_stable_structures::memory_manager::MemoryId;
+use ic_stable_structures::{Memory as _, StableCell};
 use sha2::{Sha256, Digest}; // For password hashing
-use uuid::Uuid; // For generating unique session tokens
 use serde_json::json; // For structured logging
-use chrono::{Utc, Duration}; // For managing expiration times
+use ic_cdk::api::call::{CallResult, RejectionCode};
+use ic_cdk::api::management_canister::main::raw_rand;
+use ic_cdk::api::management_canister::http_request::{
+    http_request, CanisterHttpRequestArgument, HttpHeader, HttpMethod, HttpResponse, TransformArgs, TransformContext,
+};
+
+mod env;
+mod legacy;
+mod stable_map;
+#[cfg(test)]
+mod tests;
+use stable_map::{memory, stable_memory, Memory, StableMap, StableValue};
 
 // Custom Error Type
-#[derive(Debug, CandidType, Deserialize)]
+#[derive(Debug)]
 enum AppError {
     UserAlreadyExists,
     InvalidCredentials,
@@ -18,16 +32,70 @@ enum AppError {
     CourseAlreadyExists,
     QuizAlreadyExists,
     RequiredCoursesNotCompleted,
+    #[allow(dead_code)] // No longer raised; kept so its code stays reserved
     FeedbackError,
     StorageError(String),
+    #[allow(dead_code)] // No longer raised; kept so its code stays reserved
     NotificationError,
     InvalidReward,
+    QuizNotFound,
+    MaxAttemptsReached,
+    QuizNotStarted,
+    QuizTimedOut,
+    Unauthorized,
+    InvalidQuizStructure,
+    CourseNotFound,
+    CourseInUse,
+    GoalNotFound,
+    TeamNotFound,
+    ChallengeNotAvailableYet,
+    InvalidResetToken,
+    ResetTokenExpired,
+    EmailNotVerified,
+    InvalidVerificationToken,
+    ExportError(String),
+    InvalidTierThresholds,
+    SelfReferral,
+    FeedbackNotFound,
+    InvalidRating,
+    InputTooLong(String),
+    NotEnrolled,
+    AccountSuspended,
+    LastAdmin,
+    AdminAlreadyExists,
+    InvalidPassThreshold,
+    AlreadyParticipated,
+    InvalidRewardMultiplier,
+    InvalidCoordinates,
+    HttpOutcallError(String),
+    LedgerNotConfigured,
+    InsufficientTokens,
+    PrincipalNotLinked,
+    LedgerError(String),
+    AchievementNotOwned,
+    UnsupportedLanguage,
+    RewardTooLarge,
+    ResourceAlreadyExists,
+    ResourceNotFound,
+    EmailAlreadyExists,
+    QuizOnCooldown(u64), // Seconds remaining
+    TransferLimitExceeded,
+    CertificateNotFound,
+    ReplayedRequest,
+    QuizAlreadyStarted,
+    InvalidSetting(String),
+    NotInvited,
+    CashOutPending(String),
+    CashOutNotFound,
+    BalanceOverflow,
+    TooManyRequests(u64), // Seconds until the next request is allowed
+    ChallengeEnded,
 }
 
 // Implementing Display for AppError for easier debugging
-impl ToString for AppError {
-    fn to_string(&self) -> String {
-        match self {
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
             AppError::UserAlreadyExists => "User already exists".to_string(),
             AppError::InvalidCredentials => "Invalid credentials".to_string(),
             AppError::UserNotFound => "User not found".to_string(),
@@ -40,87 +108,679 @@ impl ToString for AppError {
             AppError::StorageError(e) => format!("Storage error: {}", e),
             AppError::NotificationError => "Error sending notification".to_string(),
             AppError::InvalidReward => "Invalid reward request".to_string(),
+            AppError::QuizNotFound => "Quiz not found".to_string(),
+            AppError::MaxAttemptsReached => "Maximum number of attempts reached for this quiz".to_string(),
+            AppError::QuizNotStarted => "Quiz has not been started".to_string(),
+            AppError::QuizTimedOut => "Quiz time limit exceeded".to_string(),
+            AppError::Unauthorized => "Unauthorized".to_string(),
+            AppError::InvalidQuizStructure => "Quiz questions, options and answers do not match".to_string(),
+            AppError::CourseNotFound => "Course not found".to_string(),
+            AppError::CourseInUse => "Course is required by an existing challenge".to_string(),
+            AppError::GoalNotFound => "No footprint goal set".to_string(),
+            AppError::TeamNotFound => "Team not found".to_string(),
+            AppError::ChallengeNotAvailableYet => "Challenge cannot be repeated yet".to_string(),
+            AppError::InvalidResetToken => "Invalid password reset token".to_string(),
+            AppError::ResetTokenExpired => "Password reset token expired".to_string(),
+            AppError::EmailNotVerified => "Email address has not been verified".to_string(),
+            AppError::InvalidVerificationToken => "Invalid email verification token".to_string(),
+            AppError::ExportError(e) => format!("Export error: {}", e),
+            AppError::InvalidTierThresholds => "Tier thresholds must be positive and strictly increasing".to_string(),
+            AppError::SelfReferral => "Users cannot refer themselves".to_string(),
+            AppError::FeedbackNotFound => "Feedback not found".to_string(),
+            AppError::InvalidRating => "Rating must be between 1 and 5".to_string(),
+            AppError::InputTooLong(e) => format!("Input too long: {}", e),
+            AppError::NotEnrolled => "User is not enrolled in this course".to_string(),
+            AppError::AccountSuspended => "Account is suspended".to_string(),
+            AppError::LastAdmin => "Cannot demote the last remaining admin".to_string(),
+            AppError::AdminAlreadyExists => "An admin already exists".to_string(),
+            AppError::InvalidPassThreshold => "Pass threshold must be between 1 and 100".to_string(),
+            AppError::AlreadyParticipated => "User has already participated in this challenge".to_string(),
+            AppError::InvalidRewardMultiplier => "Reward multiplier must be positive with a window that ends after it starts".to_string(),
+            AppError::InvalidCoordinates => "Latitude or longitude out of range".to_string(),
+            AppError::HttpOutcallError(e) => format!("HTTP outcall failed: {}", e),
+            AppError::LedgerNotConfigured => "No ledger canister configured".to_string(),
+            AppError::InsufficientTokens => "Insufficient tokens".to_string(),
+            AppError::PrincipalNotLinked => "User has no linked principal".to_string(),
+            AppError::LedgerError(e) => format!("Ledger transfer failed: {}", e),
+            AppError::AchievementNotOwned => "User does not hold this achievement".to_string(),
+            AppError::UnsupportedLanguage => "Language is not supported".to_string(),
+            AppError::RewardTooLarge => "Reward exceeds the configured maximum".to_string(),
+            AppError::ResourceAlreadyExists => "Educational resource already exists".to_string(),
+            AppError::ResourceNotFound => "Educational resource not found".to_string(),
+            AppError::EmailAlreadyExists => "Email address is already registered".to_string(),
+            AppError::QuizOnCooldown(remaining) => format!("Quiz is on cooldown for another {} seconds", remaining),
+            AppError::TransferLimitExceeded => "Transfer exceeds the allowed limit".to_string(),
+            AppError::CertificateNotFound => "Certificate not found".to_string(),
+            AppError::ReplayedRequest => "Request nonce has already been used".to_string(),
+            AppError::QuizAlreadyStarted => "Quiz is already in progress".to_string(),
+            AppError::InvalidSetting(e) => format!("Invalid setting: {}", e),
+            AppError::NotInvited => "No pending invitation to this team".to_string(),
+            AppError::CashOutPending(e) => format!("Cash-out outcome unknown, held for reconciliation: {}", e),
+            AppError::CashOutNotFound => "Pending cash-out not found".to_string(),
+            AppError::BalanceOverflow => "Token balance would exceed its maximum".to_string(),
+            AppError::TooManyRequests(remaining) => format!("Too many requests; try again in {} seconds", remaining),
+            AppError::ChallengeEnded => "Challenge has ended".to_string(),
+        };
+        f.write_str(&message)
+    }
+}
+
+// Stable numeric codes for the frontend; never renumber or reuse a code
+impl AppError {
+    fn code(&self) -> u32 {
+        match self {
+            AppError::UserAlreadyExists => 1,
+            AppError::InvalidCredentials => 2,
+            AppError::UserNotFound => 3,
+            AppError::SessionTokenExpired => 4,
+            AppError::ChallengeNotFound => 5,
+            AppError::CourseAlreadyExists => 6,
+            AppError::QuizAlreadyExists => 7,
+            AppError::RequiredCoursesNotCompleted => 8,
+            AppError::FeedbackError => 9,
+            AppError::StorageError(_) => 10,
+            AppError::NotificationError => 11,
+            AppError::InvalidReward => 12,
+            AppError::QuizNotFound => 13,
+            AppError::MaxAttemptsReached => 14,
+            AppError::QuizNotStarted => 15,
+            AppError::QuizTimedOut => 16,
+            AppError::Unauthorized => 17,
+            AppError::InvalidQuizStructure => 18,
+            AppError::CourseNotFound => 19,
+            AppError::CourseInUse => 20,
+            AppError::GoalNotFound => 21,
+            AppError::TeamNotFound => 22,
+            AppError::ChallengeNotAvailableYet => 23,
+            AppError::InvalidResetToken => 24,
+            AppError::ResetTokenExpired => 25,
+            AppError::EmailNotVerified => 26,
+            AppError::InvalidVerificationToken => 27,
+            AppError::ExportError(_) => 28,
+            AppError::InvalidTierThresholds => 29,
+            AppError::SelfReferral => 30,
+            AppError::FeedbackNotFound => 31,
+            AppError::InvalidRating => 32,
+            AppError::InputTooLong(_) => 33,
+            AppError::NotEnrolled => 34,
+            AppError::AccountSuspended => 35,
+            AppError::LastAdmin => 36,
+            AppError::AdminAlreadyExists => 37,
+            AppError::InvalidPassThreshold => 38,
+            AppError::AlreadyParticipated => 39,
+            AppError::InvalidRewardMultiplier => 40,
+            AppError::InvalidCoordinates => 41,
+            AppError::HttpOutcallError(_) => 42,
+            AppError::LedgerNotConfigured => 43,
+            AppError::InsufficientTokens => 44,
+            AppError::PrincipalNotLinked => 45,
+            AppError::LedgerError(_) => 46,
+            AppError::AchievementNotOwned => 47,
+            AppError::UnsupportedLanguage => 48,
+            AppError::RewardTooLarge => 49,
+            AppError::ResourceAlreadyExists => 50,
+            AppError::ResourceNotFound => 51,
+            AppError::EmailAlreadyExists => 52,
+            AppError::QuizOnCooldown(_) => 53,
+            AppError::TransferLimitExceeded => 54,
+            AppError::CertificateNotFound => 55,
+            AppError::ReplayedRequest => 56,
+            AppError::QuizAlreadyStarted => 57,
+            AppError::InvalidSetting(_) => 58,
+            AppError::NotInvited => 59,
+            AppError::CashOutPending(_) => 60,
+            AppError::CashOutNotFound => 61,
+            AppError::BalanceOverflow => 62,
+            AppError::TooManyRequests(_) => 63,
+            AppError::ChallengeEnded => 64,
         }
     }
 }
 
+// Error payload returned from endpoints
+#[derive(CandidType, Deserialize)]
+struct ErrorResponse {
+    code: u32,
+    message: String,
+}
+
+// Endpoints return AppError directly; over Candid it is sent as an ErrorResponse
+impl CandidType for AppError {
+    fn _ty() -> candid::types::Type {
+        ErrorResponse::ty()
+    }
+
+    fn idl_serialize<S: candid::types::Serializer>(&self, serializer: S) -> Result<(), S::Error> {
+        ErrorResponse { code: self.code(), message: self.to_string() }.idl_serialize(serializer)
+    }
+}
+
 // Structure Definitions
-#[derive(Clone, Debug, CandidType, Deserialize)]
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 struct User {
     id: String,
     full_name: String,
     email: String,
     hashed_password: String,
+    #[serde(default)]
+    salt: String,
     tokens: u32,
     role: Role,
     preferred_language: String,
-    session_token: Option<SessionToken>,
+    #[serde(default)]
+    sessions: Vec<SessionToken>, // One live session per device
     achievements: Vec<String>,
     challenges_completed: Vec<String>,
     completed_courses: Vec<String>,
+    #[serde(default)]
+    enrolled_courses: Vec<String>, // Courses being taken but not yet completed
+    #[serde(default)]
+    unlocked_courses: Vec<String>, // Paid courses already charged for
     passed_quizzes: Vec<String>,
     notifications: Vec<String>, // For social notifications
+    #[serde(default)]
+    quiz_attempts: HashMap<String, u32>, // Attempts per quiz title
+    #[serde(default)]
+    last_quiz_attempts: HashMap<String, i64>, // Quiz title -> time of the latest attempt
+    #[serde(default)]
+    last_challenge_participation: HashMap<String, i64>, // Challenge id -> last participation time
+    #[serde(default)]
+    password_reset: Option<ResetToken>, // One-time password reset token
+    #[serde(default)]
+    email_verified: bool,
+    #[serde(default)]
+    email_verification_token: Option<String>,
+    #[serde(default)]
+    last_login: i64, // Time of the latest login; 0 if never logged in
+    #[serde(default)]
+    last_login_day: i64, // Days since the epoch of the latest login
+    #[serde(default)]
+    streak: u32, // Consecutive days with a login
+    #[serde(default)]
+    suspended: bool, // Set by moderators; blocks login and session use
+    #[serde(default)]
+    principal: Option<Principal>, // Wallet allowed to transfer this user's tokens over ICRC-1
+    #[serde(default)]
+    profile_bonus_awarded: bool,
+    #[serde(default)]
+    onboarding: OnboardingProgress,
+    #[serde(default)]
+    last_recycling_fetch: i64, // Time of the latest recycling center outcall made for this user
+}
+
+// Onboarding checklist; the bonus is paid once every step is done
+#[derive(Clone, Debug, Default, CandidType, Deserialize, Serialize)]
+struct OnboardingProgress {
+    email_verified: bool,
+    first_course_completed: bool,
+    first_quiz_passed: bool,
+    first_challenge_joined: bool,
+    bonus_awarded: bool,
+}
+
+#[derive(Clone, Copy)]
+enum OnboardingStep {
+    EmailVerified,
+    CourseCompleted,
+    QuizPassed,
+    ChallengeJoined,
+}
+
+// User record without credentials or sessions, safe to return to other users
+#[derive(Clone, CandidType, Deserialize)]
+struct UserProfile {
+    id: String,
+    full_name: String,
+    email: String,
+    tokens: u32,
+    role: Role,
+    preferred_language: String,
+    achievements: Vec<String>,
+    completed_courses: Vec<String>,
+    email_verified: bool,
+}
+
+// Profile returned to its owner on a session-checked query
+#[derive(Clone, CandidType, Deserialize)]
+struct SessionProfile {
+    profile: UserProfile,
+    session_expiring: bool, // Session is past expiry but within the query grace period
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct ImportUser {
+    id: String,
+    full_name: String,
+    email: String,
+    password: String,
+    role: Role,
+    preferred_language: String,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct ImportReport {
+    created: Vec<String>,
+    skipped: Vec<String>,
 }
 
-#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
 enum Role {
     Admin,
     User,
 }
 
-#[derive(Clone, CandidType, Deserialize)]
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct UserFootprint {
     waste_generated: u32,
     recyclable_waste: u32,
     footprint_score: f32,
 }
 
+// Community totals over each user's latest footprint
+#[derive(Clone, CandidType, Deserialize)]
+struct FootprintSummary {
+    total_waste_generated: u64,
+    total_recyclable_waste: u64,
+    average_footprint_score: f32,
+    users_counted: u32,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct FootprintGoal {
+    target_score: f32,
+    deadline: i64,
+    achieved: bool,
+}
+
 #[derive(Clone, CandidType, Deserialize)]
+struct GoalProgress {
+    current_score: f32,
+    target_score: f32,
+    deadline: i64,
+    deadline_passed: bool,
+    achieved: bool,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Quiz {
     level: u32,
     questions: Vec<String>,
     options: Vec<Vec<String>>,
     correct_answers: Vec<String>,
     reward: u32,
+    #[serde(default)]
+    time_limit_secs: u64, // 0 means the quiz is untimed
+    #[serde(default = "default_pass_threshold")] // Quizzes stored before thresholds were configurable
+    pass_threshold: u8, // Percentage of correct answers required to pass
+    #[serde(default)]
+    cooldown_secs: u64, // Minimum wait between attempts; 0 allows immediate retries
+    #[serde(default)]
+    difficulty: Difficulty,
+}
+
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, Serialize, PartialEq)]
+enum Difficulty {
+    #[default]
+    Easy,
+    Medium,
+    Hard,
+}
+
+// Reward multiplier applied to a quiz's base reward for each difficulty
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct DifficultyMultipliers {
+    easy: f32,
+    medium: f32,
+    hard: f32,
+}
+
+fn default_pass_threshold() -> u8 {
+    DEFAULT_QUIZ_PASS_THRESHOLD
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct QuizResult {
+    score: u32,
+    total: u32,
+    passed: bool,
+    per_question: Vec<bool>, // Whether each question was answered correctly
+    already_passed: bool, // Passed on an earlier submission, so no reward was paid this time
+}
+
+// Proof of a completed course; hash is the hex SHA-256 of
+// "<user_id>|<course_title>|<issued_at>"
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct Certificate {
+    id: String,
+    user_id: String,
+    course_title: String,
+    issued_at: i64,
+    hash: String,
+}
+
+// A user's first pass of a quiz
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct QuizPassRecord {
+    quiz_title: String,
+    score: u32,
+    total: u32,
+    passed_at: i64,
+}
+
+// Shape check of an answer sheet; says nothing about correctness
+#[derive(Clone, CandidType, Deserialize)]
+struct ValidationReport {
+    expected_count: u32,
+    count_matches: bool,
+    valid_answers: Vec<bool>, // Whether each answer is one of its question's options
+}
+
+// A started quiz as shown to the user; with shuffling, questions and options
+// appear in a per-attempt order and answers are submitted in that order
+#[derive(Clone, CandidType, Deserialize)]
+struct QuizSession {
+    started_at: i64,
+    difficulty: Difficulty,
+    questions: Vec<String>,
+    options: Vec<Vec<String>>,
 }
 
 #[derive(Clone, CandidType, Deserialize)]
+struct CourseResult {
+    level_results: Vec<(u32, QuizResult)>,
+    course_completed: bool,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Challenge {
     description: String,
     reward_tokens: u32,
     required_courses: Vec<String>,
     required_quizzes: Vec<String>,
     participants: Vec<String>,
+    #[serde(default)]
+    team_challenge: bool, // Completed collectively by a team, reward split among members
+    #[serde(default)]
+    recurrence_secs: Option<u64>, // Interval after which the challenge can be repeated
+    #[serde(default)]
+    category: String, // e.g. "Plastic", "E-waste", "Composting"
+    #[serde(default)]
+    ends_at: Option<i64>, // Deadline in seconds, if the challenge has one
+    #[serde(default)]
+    reminded: Vec<String>, // Users already sent a deadline reminder
 }
 
 #[derive(Clone, CandidType, Deserialize)]
+struct ChallengeSummary {
+    id: String,
+    description: String,
+    category: String,
+    reward_tokens: u32,
+    participant_count: u32,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct Team {
+    id: String,
+    name: String,
+    members: Vec<String>,
+    #[serde(default)]
+    owner: String, // Member who created the team; empty for teams created before owners
+    #[serde(default)]
+    invited: Vec<String>, // Users asked to join who have not accepted yet
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Token {
     owner: String,
     balance: u32,
 }
 
+// ICRC-1 account; internal users are subaccounts of this canister
+#[derive(Clone, CandidType, Deserialize, PartialEq)]
+struct Account {
+    owner: Principal,
+    subaccount: Option<Vec<u8>>,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct TransferArg {
+    from_subaccount: Option<Vec<u8>>,
+    to: Account,
+    amount: u128,
+    fee: Option<u128>,
+    memo: Option<Vec<u8>>,
+    created_at_time: Option<u64>,
+}
+
 #[derive(Clone, Debug, CandidType, Deserialize)]
+enum TransferError {
+    BadFee { expected_fee: u128 },
+    BadBurn { min_burn_amount: u128 },
+    InsufficientFunds { balance: u128 },
+    TooOld,
+    CreatedInFuture { ledger_time: u64 },
+    Duplicate { duplicate_of: u128 },
+    TemporarilyUnavailable,
+    GenericError { error_code: u128, message: String },
+}
+
+// ICRC-1 transfer that carried a created_at_time, kept while a retry of it
+// could still arrive so the retry is reported as a duplicate
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct RecentTransfer {
+    created_at_time: u64,
+    block_index: u128,
+}
+
+// Cash-out whose tokens are held until the ledger transfer is known to
+// have completed or failed
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct PendingCashOut {
+    id: String,
+    user_id: String,
+    amount: u32,
+    to: Principal,
+    created_at_time: u64, // Sent with the transfer so the ledger deduplicates a retry
+}
+
+// What a ledger transfer call is known to have done
+#[derive(Clone, Debug)]
+enum TransferOutcome {
+    Completed(u128), // Block index
+    Rejected(String), // Definitely not executed
+    Unknown(String), // May or may not have executed
+}
+
+#[derive(Clone, Default, CandidType, Deserialize, Serialize)]
+struct TokenSupply {
+    total_minted: u64,
+    total_burned: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct TokenTxn {
+    delta: i64,
+    reason: String,
+    timestamp: i64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+enum ActivityKind {
+    QuizPassed,
+    CourseEnrolled,
+    CourseCompleted,
+    ChallengeJoined,
+    RewardRedeemed,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct ActivityEvent {
+    kind: ActivityKind,
+    subject: String, // Quiz title, course title, challenge id or reward id
+    timestamp: i64,
+}
+
+#[derive(Clone, Copy, Debug, CandidType, Deserialize, PartialEq)]
+enum Tier {
+    Bronze,
+    Silver,
+    Gold,
+    Platinum,
+}
+
+// Minimum token balance for each tier above Bronze
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct TierThresholds {
+    silver: u32,
+    gold: u32,
+    platinum: u32,
+}
+
+impl Default for TierThresholds {
+    fn default() -> Self {
+        TierThresholds { silver: 100, gold: 500, platinum: 1_000 }
+    }
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct ReputationInfo {
+    tier: Tier,
+    tokens: u32,
+    next_tier: Option<Tier>,
+    tokens_to_next_tier: u32,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct LeaderboardEntry {
+    rank: u32, // 1-based position by tokens
+    user_id: String,
+    tokens: u32,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
 struct SessionToken {
+    #[serde(default)]
+    device_id: String,
     token: String,
     expires_at: i64,
 }
 
-#[derive(Clone, CandidType, Deserialize)]
+#[derive(Clone, CandidType, Deserialize, PartialEq)]
+enum SessionStatus {
+    Valid,
+    Expired,
+    NotFound, // Also returned for unknown or suspended users
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize)]
+struct ResetToken {
+    token: String,
+    expires_at: i64,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Course {
     title: String,
     levels: HashMap<u32, Quiz>,
     educational_resources: Vec<String>, // Educational resources
+    #[serde(default)]
+    prerequisites: Vec<String>, // Courses that must be completed first
+    #[serde(default)]
+    token_cost: u32, // Tokens charged once to unlock; 0 for free courses
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
+struct RecyclingCenter {
+    id: u64, // OpenStreetMap node id
+    name: String,
+    lat: f64,
+    lon: f64,
+    materials: Vec<String>, // e.g. "glass", "paper", "batteries"
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct PurgeReport {
+    notifications: u64,
+    log_entries: u64,
+    token_history: u64,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct SystemStats {
+    total_users: u64,
+    total_courses: u64,
+    total_challenges: u64,
+    tokens_in_circulation: u64,
+    total_feedback: u64,
+    active_sessions: u64,
+}
+
+// Approximate CBOR-encoded size of each collection, in bytes
+#[derive(Clone, CandidType, Deserialize)]
+struct StorageUsage {
+    users_bytes: u64,
+    courses_bytes: u64,
+    notifications_bytes: u64,
+    log_bytes: u64,
+    state_bytes: u64, // Everything kept in the single state record, log included
+    stable_memory_pages: u64, // 64 KiB WebAssembly pages
 }
 
 #[derive(Clone, CandidType, Deserialize)]
+struct ExportChunk {
+    data: String,
+    chunk: u32,
+    total_chunks: u32,
+}
+
+// Public view of a course; level quizzes are left out so answers stay private
+#[derive(Clone, CandidType, Deserialize)]
+struct CourseSummary {
+    title: String,
+    level_count: u32,
+    educational_resources: Vec<String>,
+    prerequisites: Vec<String>,
+    token_cost: u32,
+    average_rating: Option<f32>, // None until the course is rated
+    completions: u32,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+enum ActionKind {
+    VerifyEmail,
+    EnrollInCourse,
+    CompleteQuiz,
+    CompleteCourse,
+    ParticipateInChallenge,
+    Done,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct RecommendedAction {
+    kind: ActionKind,
+    target: Option<String>, // Course title, quiz title or challenge id the action refers to
+    message: String,
+}
+
+#[derive(Clone, CandidType, Deserialize)]
+struct CourseProgress {
+    total_levels: u32,
+    passed_levels: u32,
+    percent_complete: f32,
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Notification {
     user_id: String,
     message: String,
     timestamp: i64,
     notification_type: NotificationType,
+    #[serde(default)]
+    read: bool,
 }
 
-#[derive(Clone, CandidType, Deserialize)]
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq, Hash)]
 enum NotificationType {
     CourseAdded,
     ChallengeAdded,
@@ -128,53 +788,249 @@ enum NotificationType {
     ChallengeParticipated,
     ChallengeReminder,
     AchievementShared, // New notification type for achievements
+    FeedbackResponse,
+    RewardRedeemed,
+    Announcement,
+    OnboardingComplete,
+    LowCycles,
 }
 
-#[derive(Clone, CandidType, Deserialize)]
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Feedback {
+    #[serde(default)]
+    id: String,
     user_id: String,
     feedback: String,
+    #[serde(default)]
+    status: FeedbackStatus,
+    #[serde(default)]
+    response: Option<String>,
+    #[serde(default)]
+    category: FeedbackCategory,
+    #[serde(default)]
+    rating: Option<u8>, // 1 to 5
+}
+
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, Serialize, PartialEq)]
+enum FeedbackStatus {
+    #[default]
+    New,
+    InProgress,
+    Resolved,
+}
+
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, Serialize, PartialEq)]
+enum FeedbackCategory {
+    Bug,
+    Feature,
+    Content,
+    #[default]
+    Other,
 }
 
 #[derive(Clone, CandidType, Deserialize)]
+struct FeedbackStats {
+    counts_by_category: Vec<(FeedbackCategory, u32)>,
+    average_rating: Option<f32>, // None until some feedback is rated
+}
+
+#[derive(Clone, CandidType, Deserialize, Serialize)]
 struct Reward {
     id: String,
     description: String,
     cost_tokens: u32,
+    #[serde(default)]
+    category: String,
+}
+
+// Cheapest reward still out of reach; reward is None when the user can afford
+// everything, including when the catalog is empty
+#[derive(Clone, CandidType, Deserialize)]
+struct NearestReward {
+    reward: Option<Reward>,
+    tokens_needed: u32,
+    all_affordable: bool,
 }
 
-type Users = HashMap<String, User>;
-type Footprints = HashMap<String, UserFootprint>;
+#[derive(Clone, CandidType, Deserialize)]
+struct RewardWithAffordability {
+    reward: Reward,
+    can_afford: bool,
+}
+
+type Users = StableMap<User>;
+type Footprints = HashMap<String, Vec<(i64, UserFootprint)>>; // Timestamped footprint history per user
 type Quizzes = HashMap<String, Quiz>;
 type Challenges = HashMap<String, Challenge>;
 type Ledger = HashMap<String, Token>;
 type ActionLog = Vec<String>;
-type Courses = HashMap<String, Course>;
-type Notifications = HashMap<String, Vec<Notification>>;
+type Courses = StableMap<Course>;
+type Notifications = StableMap<Vec<Notification>>;
 type Feedbacks = Vec<Feedback>;
 type Rewards = HashMap<String, Reward>;
+type QuizStarts = HashMap<String, HashMap<String, i64>>; // User id -> quiz title -> start time
+type QuizOrders = HashMap<String, HashMap<String, Vec<u32>>>; // User id -> quiz title -> canonical index of each shown question
+type TokenHistories = HashMap<String, Vec<TokenTxn>>; // Balance changes per user
+type ActivityFeeds = HashMap<String, Vec<ActivityEvent>>; // A user's own significant actions
+type QuizPasses = HashMap<String, Vec<QuizPassRecord>>; // Pass details behind User::passed_quizzes
+type Certificates = HashMap<String, Certificate>; // Certificate id -> certificate
+type RankSnapshot = HashMap<String, u32>; // User id -> 1-based leaderboard rank
+type RequestNonces = HashMap<String, u64>; // User id -> highest nonce accepted on a sensitive update
+type TransferWindows = HashMap<String, (i64, u32)>; // User id -> (window start, tokens sent in window)
+type FootprintGoals = HashMap<String, FootprintGoal>;
+type RecentTransfers = HashMap<String, RecentTransfer>; // Transaction hash -> transfer inside the deduplication window
+type PendingCashOuts = HashMap<String, PendingCashOut>; // Cash-out id -> cash-out awaiting its outcome
+type Teams = HashMap<String, Team>;
+type Referrals = HashMap<String, String>; // Referee id -> referrer id
+type CourseRatings = HashMap<String, Vec<(String, u8)>>; // Course title -> (user id, rating)
+type EmailIndex = HashMap<String, String>; // Normalized email -> user id
+type CourseCompletions = HashMap<String, u32>; // Course title -> users who completed it
+type RecyclingCenterCache = StableMap<(i64, Vec<RecyclingCenter>)>; // Rounded "lat,lon" -> (fetched at, centers)
 
-#[init]
-fn init() {
-    storage::stable_save((
-        HashMap::<String, User>::new(),
-        HashMap::<String, UserFootprint>::new(),
-        HashMap::<String, Quiz>::new(),
-        HashMap::<String, Challenge>::new(),
-        HashMap::<String, Token>::new(),
-        Vec::<String>::new(),
-        HashMap::<String, Course>::new(),
-        HashMap::<String, Vec<Notification>>::new(),
-        Vec::<Feedback>::new(),
-        HashMap::<String, Reward>::new(),
-    )).unwrap();
+// Stable memory layout. Users, courses, notifications and cached recycling
+// centers live in their own StableBTreeMap regions; everything else is
+// persisted as one value.
+const STATE_MEMORY_ID: MemoryId = MemoryId::new(0);
+const ID_COUNTER_MEMORY_ID: MemoryId = MemoryId::new(4);
+
+impl StableValue for User {
+    const MEMORY_ID: MemoryId = MemoryId::new(1);
+}
+
+impl StableValue for Course {
+    const MEMORY_ID: MemoryId = MemoryId::new(2);
+}
+
+impl StableValue for Vec<Notification> {
+    const MEMORY_ID: MemoryId = MemoryId::new(3);
+}
+
+impl StableValue for (i64, Vec<RecyclingCenter>) {
+    const MEMORY_ID: MemoryId = MemoryId::new(5);
+}
+
+thread_local! {
+    static STATE: RefCell<StableCell<Vec<u8>, Memory>> = RefCell::new(
+        StableCell::init(memory(STATE_MEMORY_ID), Vec::new()).expect("failed to initialize state cell")
+    );
+    static NOTIFICATION_TEMPLATES: HashMap<(NotificationType, String), String> = notification_templates();
+    static ID_COUNTER: RefCell<StableCell<u64, Memory>> = RefCell::new(
+        StableCell::init(memory(ID_COUNTER_MEMORY_ID), 0).expect("failed to initialize id counter")
+    );
+    static ID_SEED: RefCell<[u8; 32]> = const { RefCell::new([0; 32]) }; // Drawn from raw_rand after init and every upgrade
+}
+
+#[cfg(test)]
+thread_local! {
+    static SAVES: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+// State saves so far, to check how often an update writes stable memory
+#[cfg(test)]
+fn saves() -> u64 {
+    SAVES.with(std::cell::Cell::get)
+}
+
+// Unique, hard-to-guess id for records, salts and tokens. Hashes a secret
+// seed with the time and a counter kept in stable memory, so every replica
+// derives the same value and no two calls share one.
+fn generate_id() -> String {
+    let counter = ID_COUNTER.with(|cell| {
+        let mut cell = cell.borrow_mut();
+        let next = *cell.get() + 1;
+        cell.set(next).expect("failed to update id counter");
+        next
+    });
+
+    let mut hasher = Sha256::new();
+    ID_SEED.with(|seed| hasher.update(*seed.borrow()));
+    hasher.update(env::time().to_be_bytes());
+    hasher.update(counter.to_be_bytes());
+    format!("{:x}", hasher.finalize())[..32].to_string()
+}
+
+async fn seed_id_generator() {
+    let result = match raw_rand().await {
+        Ok((bytes,)) if bytes.len() >= 32 => {
+            ID_SEED.with(|seed| seed.borrow_mut().copy_from_slice(&bytes[..32]));
+            Ok(())
+        }
+        Ok(_) => Err("raw_rand returned too few bytes".to_string()),
+        Err((code, message)) => Err(format!("{:?} {}", code, message)),
+    };
+    log_timer_failure("Seeding the id generator", result);
+}
+
+const DEFAULT_LANGUAGE: &str = "en";
+
+// Notification message templates by type and language; each {} is filled in order
+fn notification_templates() -> HashMap<(NotificationType, String), String> {
+    let templates = [
+        (NotificationType::CourseAdded, "en", "New course available: {}"),
+        (NotificationType::CourseAdded, "es", "Nuevo curso disponible: {}"),
+        (NotificationType::CourseAdded, "fr", "Nouveau cours disponible : {}"),
+        (NotificationType::ChallengeAdded, "en", "New challenge: {}"),
+        (NotificationType::ChallengeAdded, "es", "Nuevo desafío: {}"),
+        (NotificationType::ChallengeAdded, "fr", "Nouveau défi : {}"),
+        (NotificationType::ChallengeStarted, "en", "Challenge started: {}"),
+        (NotificationType::ChallengeStarted, "es", "Desafío iniciado: {}"),
+        (NotificationType::ChallengeStarted, "fr", "Défi commencé : {}"),
+        (NotificationType::ChallengeParticipated, "en", "You have successfully participated in the challenge: {}"),
+        (NotificationType::ChallengeParticipated, "es", "Has participado con éxito en el desafío: {}"),
+        (NotificationType::ChallengeParticipated, "fr", "Vous avez participé avec succès au défi : {}"),
+        (NotificationType::ChallengeReminder, "en", "The challenge \"{}\" ends soon. There is still time to take part!"),
+        (NotificationType::ChallengeReminder, "es", "El desafío \"{}\" termina pronto. ¡Todavía hay tiempo para participar!"),
+        (NotificationType::ChallengeReminder, "fr", "Le défi « {} » se termine bientôt. Il est encore temps d'y participer !"),
+        (NotificationType::AchievementShared, "en", "{} shared with you: {}"),
+        (NotificationType::AchievementShared, "es", "{} compartió contigo: {}"),
+        (NotificationType::AchievementShared, "fr", "{} a partagé avec vous : {}"),
+        (NotificationType::FeedbackResponse, "en", "An admin responded to your feedback: {}"),
+        (NotificationType::FeedbackResponse, "es", "Un administrador respondió a tus comentarios: {}"),
+        (NotificationType::FeedbackResponse, "fr", "Un administrateur a répondu à votre commentaire : {}"),
+        (NotificationType::RewardRedeemed, "en", "You redeemed {}. Remaining balance: {} tokens"),
+        (NotificationType::RewardRedeemed, "es", "Has canjeado {}. Saldo restante: {} tokens"),
+        (NotificationType::RewardRedeemed, "fr", "Vous avez échangé {}. Solde restant : {} jetons"),
+        (NotificationType::Announcement, "en", "Announcement: {}"),
+        (NotificationType::Announcement, "es", "Anuncio: {}"),
+        (NotificationType::Announcement, "fr", "Annonce : {}"),
+        (NotificationType::OnboardingComplete, "en", "Onboarding complete! You earned {} tokens"),
+        (NotificationType::OnboardingComplete, "es", "¡Has completado la introducción! Ganaste {} tokens"),
+        (NotificationType::OnboardingComplete, "fr", "Intégration terminée ! Vous avez gagné {} jetons"),
+        (NotificationType::LowCycles, "en", "Canister cycles are low: {} remaining"),
+        (NotificationType::LowCycles, "es", "Quedan pocos ciclos en el canister: {}"),
+        (NotificationType::LowCycles, "fr", "Le canister manque de cycles : {} restants"),
+    ];
+    templates.into_iter()
+        .map(|(notification_type, lang, template)| ((notification_type, lang.to_string()), template.to_string()))
+        .collect()
 }
 
-fn restore_storage() -> (Users, Footprints, Quizzes, Challenges, Ledger, ActionLog, Courses, Notifications, Feedbacks, Rewards) {
-    storage::stable_restore::<(Users, Footprints, Quizzes, Challenges, Ledger, ActionLog, Courses, Notifications, Feedbacks, Rewards)>().unwrap()
+// Render a notification in the given language, falling back to English.
+// Languages are matched on their primary subtag, so "es-MX" uses "es".
+fn render_notification(notification_type: NotificationType, lang: &str, args: &[&str]) -> String {
+    let lang = lang.split('-').next().unwrap_or_default().to_lowercase();
+    let template = NOTIFICATION_TEMPLATES.with(|templates| {
+        templates.get(&(notification_type.clone(), lang))
+            .or_else(|| templates.get(&(notification_type, DEFAULT_LANGUAGE.to_string())))
+            .cloned()
+            .unwrap_or_else(|| "{}".to_string())
+    });
+
+    let mut parts = template.split("{}");
+    let mut message = parts.next().unwrap_or_default().to_string();
+    for (index, part) in parts.enumerate() {
+        message.push_str(args.get(index).copied().unwrap_or_default());
+        message.push_str(part);
+    }
+    message
 }
 
-fn save_storage(
+// All canister state. Fields missing from an older record take their
+// defaults, so adding a collection needs no migration.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+struct State {
+    schema_version: u32, // SCHEMA_VERSION of the release that last saved the state
     users: Users,
     footprints: Footprints,
     quizzes: Quizzes,
@@ -184,323 +1040,3884 @@ fn save_storage(
     courses: Courses,
     notifications: Notifications,
     feedbacks: Feedbacks,
-    rewards: Rewards, // Include rewards in storage
-) -> Result<(), AppError> {
-    storage::stable_save((
-        users,
-        footprints,
-        quizzes,
-        challenges,
-        tokens,
-        log,
-        courses,
-        notifications,
-        feedbacks,
-        rewards,
-    )).map_err(|e| AppError::StorageError(e.to_string()))
+    rewards: Rewards,
+    quiz_starts: QuizStarts,
+    quiz_orders: QuizOrders,
+    supply: TokenSupply,
+    token_history: TokenHistories,
+    footprint_goals: FootprintGoals,
+    teams: Teams,
+    tier_thresholds: TierThresholds,
+    referrals: Referrals,
+    settings: Settings,
+    course_ratings: CourseRatings,
+    recycling_centers: RecyclingCenterCache,
+    transfer_count: u128, // ICRC-1 block index of the next transfer
+    course_completions: CourseCompletions,
+    email_index: EmailIndex,
+    activity: ActivityFeeds,
+    transfer_windows: TransferWindows,
+    user_count: u32, // Kept in step with users so counting needs no scan
+    quiz_passes: QuizPasses,
+    certificates: Certificates,
+    rank_snapshot: RankSnapshot, // Public leaderboard as of the last snapshot
+    request_nonces: RequestNonces,
+    low_cycles_alerted: bool, // Admins were alerted and the balance has not recovered since
+    pending_cash_outs: PendingCashOuts,
+    recent_transfer_hashes: RecentTransfers,
 }
 
-fn log_action(action: &str) -> Result<(), AppError> {
-    let (_, _, _, _, _, mut log, _, _, _, _) = restore_storage();
-    log.push(json!({ "action": action, "timestamp": ic_cdk::api::time() as i64 }).to_string()); // Convert timestamp
-    save_storage(
-        HashMap::<String, User>::new(),
-        HashMap::<String, UserFootprint>::new(),
-        HashMap::<String, Quiz>::new(),
-        HashMap::<String, Challenge>::new(),
-        HashMap::<String, Token>::new(),
-        log,
-        HashMap::<String, Course>::new(),
-        HashMap::<String, Vec<Notification>>::new(),
-        Vec::<Feedback>::new(),
-        HashMap::<String, Reward>::new(), // Include rewards in storage
-    )?;
-    Ok(())
+// Admin-tunable values; settings missing from an older record take their defaults
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
+struct Settings {
+    referral_bonus: u32,
+    max_streak_bonus: u32,
+    reward_multiplier: f32, // Applied to quiz and challenge rewards inside the window below
+    multiplier_starts_at: i64,
+    multiplier_ends_at: i64,
+    recycling_cache_ttl_secs: u64,
+    ledger_canister_id: Option<Principal>, // External ICRC-1 ledger that cash_out pays from
+    signup_bonus: u32,
+    profile_completion_bonus: u32,
+    supported_languages: Vec<String>, // Language codes users may choose
+    max_challenge_reward: u32,
+    max_quiz_reward: u32, // Largest base reward a quiz may be created or edited with
+    max_quiz_attempts: u32, // Attempts per quiz before it must be passed
+    max_transfer_amount: u32, // Largest single transfer between users
+    daily_transfer_cap: u32, // Most a user may send within a day
+    difficulty_multipliers: DifficultyMultipliers,
+    session_grace_secs: i64, // How long an expired session still serves read-only queries
+    challenge_milestones: Vec<u32>, // Completed challenge counts that earn a Challenge Streak achievement
+    challenge_milestone_bonus: u32,
+    onboarding_bonus: u32, // Paid once the onboarding checklist is complete
+    max_notifications: u32, // Per-user backlog; the oldest are dropped beyond it
+    low_cycles_threshold: u64, // Admins are alerted when the cycles balance drops below this
 }
 
-// Hash Password with Salt
-fn hash_password(password: &str, salt: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(password);
-    hasher.update(salt);
-    format!("{:x}", hasher.finalize())
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            referral_bonus: 50,
+            max_streak_bonus: 50,
+            reward_multiplier: 1.0,
+            multiplier_starts_at: 0,
+            multiplier_ends_at: 0,
+            recycling_cache_ttl_secs: 24 * 60 * 60,
+            ledger_canister_id: None,
+            signup_bonus: 10,
+            profile_completion_bonus: 25,
+            supported_languages: vec!["en".to_string(), "es".to_string(), "fr".to_string()],
+            max_challenge_reward: DEFAULT_MAX_CHALLENGE_REWARD,
+            max_quiz_reward: DEFAULT_MAX_QUIZ_REWARD,
+            max_quiz_attempts: DEFAULT_MAX_QUIZ_ATTEMPTS,
+            max_transfer_amount: 500,
+            daily_transfer_cap: 1_000,
+            difficulty_multipliers: DifficultyMultipliers { easy: 1.0, medium: 1.5, hard: 2.0 },
+            session_grace_secs: 5 * 60,
+            challenge_milestones: vec![5, 10, 25],
+            challenge_milestone_bonus: 50,
+            onboarding_bonus: 100,
+            max_notifications: DEFAULT_MAX_NOTIFICATIONS,
+            low_cycles_threshold: 1_000_000_000_000,
+        }
+    }
 }
 
-// User Registration
-#[update]
-fn register_user(id: String, full_name: String, email: String, password: String, role: Option<Role>, preferred_language: String) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, _, _, _) = restore_storage();
+// Weight a quiz's base reward by its difficulty
+fn quiz_reward(settings: &Settings, quiz: &Quiz) -> u32 {
+    let multiplier = match quiz.difficulty {
+        Difficulty::Easy => settings.difficulty_multipliers.easy,
+        Difficulty::Medium => settings.difficulty_multipliers.medium,
+        Difficulty::Hard => settings.difficulty_multipliers.hard,
+    };
+    boosted_reward(settings, (quiz.reward as f32 * multiplier).round() as u32)
+}
 
-    if users.contains_key(&id) {
-        return Err(AppError::UserAlreadyExists);
+// Apply the promotional multiplier if its window is active
+fn boosted_reward(settings: &Settings, reward: u32) -> u32 {
+    let current_time = now();
+    if current_time >= settings.multiplier_starts_at && current_time < settings.multiplier_ends_at {
+        (reward as f32 * settings.reward_multiplier).round() as u32
+    } else {
+        reward
     }
+}
 
-    let salt = Uuid::new_v4().to_string(); // Generate salt
-    let hashed_password = hash_password(&password, &salt);
-    let user_role = role.unwrap_or(Role::User);
+const PASSWORD_RESET_TTL_SECS: i64 = 15 * 60;
+const SESSION_TTL_SECS: i64 = 60 * 60;
+const SESSION_SWEEP_INTERVAL_SECS: u64 = 60 * 60;
+const RANK_SNAPSHOT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+const CYCLES_CHECK_INTERVAL_SECS: u64 = 60 * 60;
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+const CHALLENGE_REMINDER_WINDOW_SECS: i64 = 24 * 60 * 60; // How close to its deadline a challenge triggers reminders
+const DEFAULT_MAX_CHALLENGE_REWARD: u32 = 1_000;
+const DEFAULT_MAX_QUIZ_REWARD: u32 = 500;
+const DEFAULT_MAX_QUIZ_ATTEMPTS: u32 = 3;
+const DEFAULT_MAX_NOTIFICATIONS: u32 = 200;
+const MAX_NOTIFICATIONS_CAP: u32 = 1_000; // Highest backlog cap an admin may set, bounding each stored list
+const STREAK_BONUS_STEP: u32 = 5; // Tokens added to the daily bonus per streak day
+const DEFAULT_QUIZ_PASS_THRESHOLD: u8 = 70; // Percentage of correct answers required to pass
+const EXPORT_CHUNK_BYTES: usize = 1_500_000; // Well under the IC response size limit
+const OVERPASS_API_URL: &str = "https://overpass-api.de/api/interpreter";
+const RECYCLING_SEARCH_RADIUS_METERS: u32 = 5_000;
+const RECYCLING_MAX_RESPONSE_BYTES: u64 = 500_000;
+const RECYCLING_CACHE_MAX_ENTRIES: usize = 1_000;
+const RECYCLING_FETCH_COOLDOWN_SECS: i64 = 60; // Between outcalls made for the same user
+const HTTP_OUTCALL_CYCLES: u128 = 30_000_000_000;
+const ICRC1_MAX_MEMO_BYTES: usize = 32;
+const ICRC1_TX_WINDOW_NANOS: u64 = 24 * 60 * 60 * 1_000_000_000;
+const ICRC1_PERMITTED_DRIFT_NANOS: u64 = 60 * 1_000_000_000;
 
-    users.insert(id.clone(), User { 
-        id: id.clone(), 
-        full_name, 
-        email, 
-        hashed_password, 
-        tokens: 0, 
-        role: user_role, 
-        preferred_language,
-        session_token: None,
-        achievements: Vec::new(), 
-        challenges_completed: Vec::new(), 
-        completed_courses: Vec::new(), 
-        passed_quizzes: Vec::new(),
-        notifications: Vec::new(), // Initialize notifications
-    });
+// Maximum input lengths in bytes
+const MAX_ID_LEN: usize = 64;
+const MAX_NAME_LEN: usize = 100;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_LANGUAGE_LEN: usize = 16;
+const MAX_FEEDBACK_LEN: usize = 2_000;
+const MAX_MESSAGE_LEN: usize = 500;
+const MAX_QUIZ_TEXT_LEN: usize = 500; // Per quiz question, option and answer
+const MAX_RESOURCE_LEN: usize = 2_048; // Long enough for most URLs
 
-    save_storage(
-        users,
-        HashMap::<String, UserFootprint>::new(), 
-        HashMap::<String, Quiz>::new(), 
-        HashMap::<String, Challenge>::new(), 
-        HashMap::<String, Token>::new(),
-        Vec::<String>::new(),
-        HashMap::<String, Course>::new(), 
-        HashMap::<String, Vec<Notification>>::new(), 
-        Vec::<Feedback>::new(),
-        HashMap::<String, Reward>::new(),
-    )?;
-    
-    log_action(&format!("User {} registered", id))?;
-    
-    Ok("User registered successfully".to_string())
+
+#[init]
+fn init() {
+    save_storage(State::default()).unwrap();
+    start_timers();
 }
 
-// Authenticate User
-#[update]
-fn login_user(id: String, password: String) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, _, _, _) = restore_storage();
+#[post_upgrade]
+fn post_upgrade() {
+    migrate_storage();
+    start_timers();
+}
 
-    match users.get_mut(&id) {
-        Some(user) if user.hashed_password == hash_password(&password, "") => {
-            let session_token = SessionToken {
-                token: Uuid::new_v4().to_string(), // Secure token generation
-                expires_at: (Utc::now() + Duration::hours(1)).timestamp(),
-            };
-            user.session_token = Some(session_token.clone());
-            save_storage(
-                users,
-                HashMap::<String, UserFootprint>::new(),
-                HashMap::<String, Quiz>::new(),
-                HashMap::<String, Challenge>::new(),
-                HashMap::<String, Token>::new(),
-                Vec::<String>::new(),
-                HashMap::<String, Course>::new(),
-                HashMap::<String, Vec<Notification>>::new(),
-                Vec::<Feedback>::new(),
-                HashMap::<String, Reward>::new(),
-            )?;
-            Ok(session_token.token)
-        }
-        Some(_) => Err(AppError::InvalidCredentials),
-        None => Err(AppError::UserNotFound),
+// Bring whatever an earlier release left in stable memory up to this one
+fn migrate_storage() {
+    if let Some(snapshot) = legacy::read_snapshot() {
+        save_storage(legacy::into_state(snapshot)).expect("failed to migrate baseline storage");
     }
+    check_schema_version();
+    rebuild_user_indexes();
 }
 
-// Check Session Token
-fn check_session_token(user: &User) -> Result<(), AppError> {
-    if let Some(token) = &user.session_token {
-        if token.expires_at < ic_cdk::api::time() as i64 { // Convert timestamp
-            return Err(AppError::SessionTokenExpired);
-        }
-        Ok(())
-    } else {
-        Err(AppError::SessionTokenExpired)
+// Serde defaults cover added fields; a release that needs more than that
+// bumps this and converts older records in post_upgrade
+const SCHEMA_VERSION: u32 = 1;
+
+// Downgrading would silently drop whatever the newer release added
+fn check_schema_version() {
+    let version = restore_storage().schema_version;
+    assert!(version <= SCHEMA_VERSION, "state was saved by schema version {}, newer than {}", version, SCHEMA_VERSION);
+}
+
+// Accounts registered before the email index or user count existed are not
+// reflected in them yet
+fn rebuild_user_indexes() {
+    let mut state = restore_storage();
+    let mut users: Vec<User> = state.users.values().collect();
+    users.sort_by(|a, b| a.id.cmp(&b.id));
+    state.user_count = users.len() as u32;
+    for user in users {
+        state.email_index.entry(normalize_email(&user.email)).or_insert(user.id);
     }
+    save_storage(state).expect("failed to rebuild user indexes");
 }
 
-// Add a Course
+// Emails are unique regardless of case and surrounding whitespace
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+// Move a user's email index entry to a new address, rejecting one another
+// account already holds. Returns false when only the case or spacing changed.
+fn reindex_email(email_index: &mut EmailIndex, user_id: &str, previous: &str, email: &str) -> Result<bool, AppError> {
+    let (previous, email) = (normalize_email(previous), normalize_email(email));
+    if email == previous {
+        return Ok(false);
+    }
+    if email_index.contains_key(&email) {
+        return Err(AppError::EmailAlreadyExists);
+    }
+    if email_index.get(&previous).map(String::as_str) == Some(user_id) {
+        email_index.remove(&previous);
+    }
+    email_index.insert(email, user_id.to_string());
+    Ok(true)
+}
+
+// Timers do not survive upgrades, so both init and post_upgrade register them
+fn start_timers() {
+    // raw_rand is an inter-canister call, which init and post_upgrade cannot make
+    ic_cdk_timers::set_timer(Duration::ZERO, || ic_cdk::spawn(seed_id_generator()));
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(SESSION_SWEEP_INTERVAL_SECS), || log_timer_failure("Session sweep", sweep_expired_sessions()));
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(RANK_SNAPSHOT_INTERVAL_SECS), || log_timer_failure("Rank snapshot", snapshot_ranks()));
+    ic_cdk_timers::set_timer_interval(Duration::from_secs(CYCLES_CHECK_INTERVAL_SECS), || log_timer_failure("Cycles check", check_cycles_balance(env::canister_balance())));
+}
+
+// Timers and spawned tasks have no caller to return an error to, so their
+// failures go to the action log instead
+fn log_timer_failure<T, E: std::fmt::Display>(task: &str, result: Result<T, E>) {
+    if let Err(e) = result {
+        // Nothing more can be done if the log itself cannot be saved
+        let _ = log_action(&format!("{} failed: {}", task, e));
+    }
+}
+
+// Alert every admin once when the balance falls below the threshold; the
+// alert rearms after the balance recovers. Returns the number of admins notified.
+fn check_cycles_balance(balance: u64) -> Result<u32, AppError> {
+    let mut state = restore_storage();
+
+    if balance >= state.settings.low_cycles_threshold {
+        if state.low_cycles_alerted {
+            state.low_cycles_alerted = false;
+            save_storage(state)?;
+        }
+        return Ok(0);
+    }
+    if state.low_cycles_alerted {
+        return Ok(0);
+    }
+
+    let timestamp = env::time() as i64;
+    let mut notified = 0;
+    for admin in state.users.values().filter(|user| user.role == Role::Admin) {
+        push_notification(&mut state.notifications, &admin.id, state.settings.max_notifications, Notification {
+            user_id: admin.id.clone(),
+            message: render_notification(NotificationType::LowCycles, &admin.preferred_language, &[&balance.to_string()]),
+            timestamp,
+            notification_type: NotificationType::LowCycles,
+            read: false,
+        });
+        notified += 1;
+    }
+    state.low_cycles_alerted = true;
+
+    save_storage(state)?;
+
+    log_action(&format!("Low cycles alert sent to {} admins at balance {}", notified, balance))?;
+
+    Ok(notified)
+}
+
+// Record every user's current public leaderboard rank
+fn snapshot_ranks() -> Result<(), AppError> {
+    let mut state = restore_storage();
+    state.rank_snapshot = ranked_users(&state.users, Some(&Role::User))
+        .into_iter()
+        .enumerate()
+        .map(|(index, (id, _))| (id, index as u32 + 1))
+        .collect();
+    save_storage(state)
+}
+
+// Drop every session past its expiry and query grace period
+fn sweep_expired_sessions() -> Result<u32, AppError> {
+    let mut state = restore_storage();
+
+    let cutoff = now() - state.settings.session_grace_secs;
+    let mut removed = 0;
+    let expired: Vec<String> = state.users.iter()
+        .filter(|(_, user)| user.sessions.iter().any(|session| session.expires_at < cutoff))
+        .map(|(id, _)| id)
+        .collect();
+    for id in expired {
+        let sessions = &mut state.users.get_mut(&id).unwrap().sessions;
+        let before = sessions.len();
+        sessions.retain(|session| session.expires_at >= cutoff);
+        removed += (before - sessions.len()) as u32;
+    }
+
+    if removed > 0 {
+        save_storage(state)?;
+    }
+
+    Ok(removed)
+}
+
+// A state record that cannot be decoded traps, so the call is rolled back
+// instead of running on, and saving over, empty collections
+fn restore_storage() -> State {
+    let bytes = STATE.with(|cell| cell.borrow().get().clone());
+    if bytes.is_empty() {
+        return State::default();
+    }
+    serde_cbor::from_slice(&bytes).expect("failed to decode state")
+}
+
+fn save_storage(mut state: State) -> Result<(), AppError> {
+    state.schema_version = SCHEMA_VERSION;
+    let bytes = serde_cbor::to_vec(&state).map_err(|e| AppError::StorageError(e.to_string()))?;
+    STATE.with(|cell| cell.borrow_mut().set(bytes))
+        .map_err(|e| AppError::StorageError(format!("{:?}", e)))?;
+    state.users.flush();
+    state.courses.flush();
+    state.notifications.flush();
+    state.recycling_centers.flush();
+    #[cfg(test)]
+    SAVES.with(|saves| saves.set(saves.get() + 1));
+    Ok(())
+}
+
+fn log_action(action: &str) -> Result<(), AppError> {
+    let mut state = restore_storage();
+    state.log.push(json!({ "action": action, "timestamp": env::time() as i64 }).to_string()); // Convert timestamp
+    save_storage(state)
+}
+
+// Current IC time in seconds
+fn now() -> i64 {
+    (env::time() / 1_000_000_000) as i64
+}
+
+// Keep the score and time behind a newly passed quiz
+fn record_quiz_pass(passes: &mut QuizPasses, user_id: &str, quiz_title: &str, result: &QuizResult) {
+    passes.entry(user_id.to_string()).or_default().push(QuizPassRecord {
+        quiz_title: quiz_title.to_string(),
+        score: result.score,
+        total: result.total,
+        passed_at: now(),
+    });
+}
+
+fn record_activity(feeds: &mut ActivityFeeds, user_id: &str, kind: ActivityKind, subject: &str) {
+    feeds.entry(user_id.to_string()).or_default().push(ActivityEvent {
+        kind,
+        subject: subject.to_string(),
+        timestamp: now(),
+    });
+}
+
+// Record a change to a user's token balance
+fn record_token_txn(history: &mut TokenHistories, user_id: &str, delta: i64, reason: &str) {
+    history.entry(user_id.to_string()).or_default().push(TokenTxn {
+        delta,
+        reason: reason.to_string(),
+        timestamp: now(),
+    });
+}
+
+// Add tokens to a user's balance, refusing a credit that would overflow it
+fn credit_tokens(user: &mut User, amount: u32) -> Result<(), AppError> {
+    user.tokens = user.tokens.checked_add(amount).ok_or(AppError::BalanceOverflow)?;
+    Ok(())
+}
+
+// Hash Password with Salt
+fn hash_password(password: &str, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(password);
+    hasher.update(salt);
+    format!("{:x}", hasher.finalize())
+}
+
+// Reject a string field longer than max bytes
+fn validate_len(field: &str, value: &str, max: usize) -> Result<(), AppError> {
+    if value.len() > max {
+        return Err(AppError::InputTooLong(format!("{} exceeds {} bytes", field, max)));
+    }
+    Ok(())
+}
+
+// User Registration
+#[update]
+fn register_user(id: String, full_name: String, email: String, password: String, role: Option<Role>, preferred_language: String, referred_by: Option<String>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    validate_len("id", &id, MAX_ID_LEN)?;
+    validate_len("full_name", &full_name, MAX_NAME_LEN)?;
+    validate_len("email", &email, MAX_EMAIL_LEN)?;
+    validate_len("preferred_language", &preferred_language, MAX_LANGUAGE_LEN)?;
+    if state.users.contains_key(&id) {
+        return Err(AppError::UserAlreadyExists);
+    }
+    if let Some(referrer_id) = &referred_by {
+        if *referrer_id == id {
+            return Err(AppError::SelfReferral);
+        }
+        if !state.users.contains_key(referrer_id) {
+            return Err(AppError::UserNotFound);
+        }
+    }
+
+    // Admins are created through claim_first_admin or set_user_role
+    if role == Some(Role::Admin) {
+        return Err(AppError::Unauthorized);
+    }
+    if state.email_index.contains_key(&normalize_email(&email)) {
+        return Err(AppError::EmailAlreadyExists);
+    }
+
+    let user_role = role.unwrap_or(Role::User);
+    state.email_index.insert(normalize_email(&email), id.clone());
+    let mut user = new_user(id.clone(), full_name, email, &password, user_role, preferred_language);
+    if state.settings.signup_bonus > 0 {
+        user.tokens = state.settings.signup_bonus;
+        record_token_txn(&mut state.token_history, &id, user.tokens as i64, "Signup bonus");
+    }
+    state.users.insert(id.clone(), user);
+    state.user_count += 1;
+
+    // Each referee credits its referrer at most once
+    if let Some(referrer_id) = referred_by {
+        if !state.referrals.contains_key(&id) {
+            let bonus = state.settings.referral_bonus;
+            credit_tokens(state.users.get_mut(&referrer_id).unwrap(), bonus)?;
+            record_token_txn(&mut state.token_history, &referrer_id, bonus as i64, &format!("Referred {}", id));
+            state.referrals.insert(id.clone(), referrer_id);
+        }
+    }
+
+    save_storage(state)?;
+    
+    log_action(&format!("User {} registered", id))?;
+    
+    Ok("User registered successfully".to_string())
+}
+
+// Register the first Admin; only possible while no admin exists
+#[update]
+fn claim_first_admin(id: String, full_name: String, email: String, password: String, preferred_language: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    validate_len("id", &id, MAX_ID_LEN)?;
+    validate_len("full_name", &full_name, MAX_NAME_LEN)?;
+    validate_len("email", &email, MAX_EMAIL_LEN)?;
+    validate_len("preferred_language", &preferred_language, MAX_LANGUAGE_LEN)?;
+    let caller = authenticated_caller()?;
+    if state.users.values().any(|user| user.role == Role::Admin) {
+        return Err(AppError::AdminAlreadyExists);
+    }
+    if state.users.contains_key(&id) {
+        return Err(AppError::UserAlreadyExists);
+    }
+    if state.email_index.contains_key(&normalize_email(&email)) {
+        return Err(AppError::EmailAlreadyExists);
+    }
+
+    state.email_index.insert(normalize_email(&email), id.clone());
+    let mut admin = new_user(id.clone(), full_name, email, &password, Role::Admin, preferred_language);
+    admin.principal = Some(caller); // Admin calls must come from this principal
+    state.users.insert(id.clone(), admin);
+    state.user_count += 1;
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} claimed the first admin account", id))?;
+
+    Ok("Admin registered successfully".to_string())
+}
+
+// One-time bonus once a user has a name, an email and a completed course
+fn award_profile_completion_bonus(user: &mut User, settings: &Settings, history: &mut TokenHistories) -> Result<(), AppError> {
+    let complete = !user.full_name.is_empty() && !user.email.is_empty() && !user.completed_courses.is_empty();
+    if complete && !user.profile_bonus_awarded {
+        user.profile_bonus_awarded = true;
+        credit_tokens(user, settings.profile_completion_bonus)?;
+        record_token_txn(history, &user.id, settings.profile_completion_bonus as i64, "Profile completion bonus");
+    }
+    Ok(())
+}
+
+// Tick an onboarding step, paying the bonus the first time all are done
+fn advance_onboarding(user: &mut User, step: OnboardingStep, settings: &Settings, history: &mut TokenHistories, notifications: &mut Notifications) -> Result<(), AppError> {
+    let progress = &mut user.onboarding;
+    match step {
+        OnboardingStep::EmailVerified => progress.email_verified = true,
+        OnboardingStep::CourseCompleted => progress.first_course_completed = true,
+        OnboardingStep::QuizPassed => progress.first_quiz_passed = true,
+        OnboardingStep::ChallengeJoined => progress.first_challenge_joined = true,
+    }
+
+    let complete = progress.email_verified && progress.first_course_completed && progress.first_quiz_passed && progress.first_challenge_joined;
+    if complete && !progress.bonus_awarded {
+        progress.bonus_awarded = true;
+        credit_tokens(user, settings.onboarding_bonus)?;
+        record_token_txn(history, &user.id, settings.onboarding_bonus as i64, "Onboarding bonus");
+        push_notification(notifications, &user.id, settings.max_notifications, Notification {
+            user_id: user.id.clone(),
+            message: render_notification(NotificationType::OnboardingComplete, &user.preferred_language, &[&settings.onboarding_bonus.to_string()]),
+            timestamp: env::time() as i64,
+            notification_type: NotificationType::OnboardingComplete,
+            read: false,
+        });
+    }
+    Ok(())
+}
+
+// Onboarding Checklist Progress
+#[query]
+fn get_onboarding_progress(user_id: String) -> Result<OnboardingProgress, AppError> {
+    let state = restore_storage();
+    state.users.get(&user_id).map(|user| user.onboarding).ok_or(AppError::UserNotFound)
+}
+
+// Configure the Onboarding Checklist Bonus (admin only)
+#[update]
+fn set_onboarding_checklist_bonus(admin_id: String, bonus: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.onboarding_bonus = bonus;
+
+    save_storage(state)?;
+
+    log_action(&format!("Onboarding checklist bonus set to {} by {}", bonus, admin_id))?;
+
+    Ok("Onboarding checklist bonus updated".to_string())
+}
+
+// Award a Challenge Streak achievement and bonus for each milestone reached,
+// once per milestone
+fn check_milestones(user: &mut User, settings: &Settings, history: &mut TokenHistories) -> Result<(), AppError> {
+    let completed = user.challenges_completed.len() as u32;
+    for threshold in &settings.challenge_milestones {
+        let achievement = format!("Challenge Streak {}", threshold);
+        if completed >= *threshold && !user.achievements.contains(&achievement) {
+            credit_tokens(user, settings.challenge_milestone_bonus)?;
+            record_token_txn(history, &user.id, settings.challenge_milestone_bonus as i64, &format!("Reached {}", achievement));
+            user.achievements.push(achievement);
+        }
+    }
+    Ok(())
+}
+
+// Configure Challenge Milestones (admin only)
+#[update]
+fn set_challenge_milestones(admin_id: String, milestones: Vec<u32>, bonus: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    let milestones: Vec<u32> = milestones.into_iter().filter(|threshold| *threshold > 0).collect();
+    state.settings.challenge_milestones = milestones.clone();
+    state.settings.challenge_milestone_bonus = bonus;
+
+    save_storage(state)?;
+
+    log_action(&format!("Challenge milestones set to {:?} with bonus {} by {}", milestones, bonus, admin_id))?;
+
+    Ok("Challenge milestones updated".to_string())
+}
+
+// Configure Signup and Profile Completion Bonuses (admin only)
+#[update]
+fn set_onboarding_bonuses(admin_id: String, signup_bonus: u32, profile_completion_bonus: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.signup_bonus = signup_bonus;
+    state.settings.profile_completion_bonus = profile_completion_bonus;
+
+    save_storage(state)?;
+
+    log_action(&format!("Onboarding bonuses set to {} and {} by {}", signup_bonus, profile_completion_bonus, admin_id))?;
+
+    Ok("Onboarding bonuses updated".to_string())
+}
+
+// Referrals made by a User
+#[query]
+fn get_referrals(user_id: String) -> Vec<String> {
+    let state = restore_storage();
+    let mut referees: Vec<String> = state.referrals.iter()
+        .filter(|(_, referrer_id)| **referrer_id == user_id)
+        .map(|(referee_id, _)| referee_id.clone())
+        .collect();
+    referees.sort();
+    referees
+}
+
+// Configure a Reward Multiplier Window (admin only)
+#[update]
+fn set_reward_multiplier(admin_id: String, multiplier: f32, starts_at: i64, ends_at: i64) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    if !multiplier.is_finite() || multiplier <= 0.0 || starts_at >= ends_at {
+        return Err(AppError::InvalidRewardMultiplier);
+    }
+    state.settings.reward_multiplier = multiplier;
+    state.settings.multiplier_starts_at = starts_at;
+    state.settings.multiplier_ends_at = ends_at;
+
+    save_storage(state)?;
+
+    log_action(&format!("Reward multiplier {} set from {} to {} by {}", multiplier, starts_at, ends_at, admin_id))?;
+
+    Ok("Reward multiplier updated".to_string())
+}
+
+// Configure Quiz Difficulty Multipliers (admin only)
+#[update]
+fn set_difficulty_multipliers(admin_id: String, multipliers: DifficultyMultipliers) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let values = [multipliers.easy, multipliers.medium, multipliers.hard];
+    if values.iter().any(|multiplier| !multiplier.is_finite() || *multiplier <= 0.0) {
+        return Err(AppError::InvalidRewardMultiplier);
+    }
+    state.settings.difficulty_multipliers = multipliers;
+
+    save_storage(state)?;
+
+    log_action(&format!("Difficulty multipliers set to {:?} by {}", values, admin_id))?;
+
+    Ok("Difficulty multipliers updated".to_string())
+}
+
+// Configure the Challenge Reward Cap (admin only)
+#[update]
+fn set_max_challenge_reward(admin_id: String, max_reward: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.max_challenge_reward = max_reward;
+
+    save_storage(state)?;
+
+    log_action(&format!("Maximum challenge reward set to {} by {}", max_reward, admin_id))?;
+
+    Ok("Maximum challenge reward updated".to_string())
+}
+
+// Configure Quiz Attempt and Reward Limits (admin only)
+#[update]
+fn set_quiz_limits(admin_id: String, max_attempts: u32, max_reward: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    if max_attempts == 0 {
+        return Err(AppError::InvalidSetting("max_attempts must be at least 1".to_string()));
+    }
+    state.settings.max_quiz_attempts = max_attempts;
+    state.settings.max_quiz_reward = max_reward;
+
+    save_storage(state)?;
+
+    log_action(&format!("Quiz limits set to {} attempts and {} tokens by {}", max_attempts, max_reward, admin_id))?;
+
+    Ok("Quiz limits updated".to_string())
+}
+
+// Configure the Referral Bonus
+#[update]
+fn set_referral_bonus(admin_id: String, bonus: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.referral_bonus = bonus;
+
+    save_storage(state)?;
+
+    log_action(&format!("Referral bonus set to {} by {}", bonus, admin_id))?;
+
+    Ok("Referral bonus updated".to_string())
+}
+
+// Build a freshly registered user with a salted password hash
+fn new_user(id: String, full_name: String, email: String, password: &str, role: Role, preferred_language: String) -> User {
+    let salt = generate_id();
+    let hashed_password = hash_password(password, &salt);
+
+    User { 
+        id, 
+        full_name, 
+        email, 
+        hashed_password, 
+        salt,
+        tokens: 0, 
+        role, 
+        preferred_language,
+        sessions: Vec::new(),
+        achievements: Vec::new(), 
+        challenges_completed: Vec::new(), 
+        completed_courses: Vec::new(), 
+        enrolled_courses: Vec::new(),
+        unlocked_courses: Vec::new(),
+        passed_quizzes: Vec::new(),
+        notifications: Vec::new(), // Initialize notifications
+        quiz_attempts: HashMap::new(),
+        last_quiz_attempts: HashMap::new(),
+        last_challenge_participation: HashMap::new(),
+        password_reset: None,
+        email_verified: false,
+        email_verification_token: Some(generate_id()),
+        last_login: 0,
+        last_login_day: 0,
+        streak: 0,
+        suspended: false,
+        principal: None,
+        profile_bonus_awarded: false,
+        onboarding: OnboardingProgress::default(),
+        last_recycling_fetch: 0,
+    }
+}
+
+fn user_profile(user: &User) -> UserProfile {
+    UserProfile {
+        id: user.id.clone(),
+        full_name: user.full_name.clone(),
+        email: user.email.clone(),
+        tokens: user.tokens,
+        role: user.role.clone(),
+        preferred_language: user.preferred_language.clone(),
+        achievements: user.achievements.clone(),
+        completed_courses: user.completed_courses.clone(),
+        email_verified: user.email_verified,
+    }
+}
+
+// Search Users by id, name or email (admin only)
+#[query]
+fn search_users(admin_id: String, query: String, offset: u32, limit: u32) -> Result<Vec<UserProfile>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let query = query.to_lowercase();
+    Ok(state.users.values()
+        .filter(|user| {
+            user.id.to_lowercase().contains(&query)
+                || user.full_name.to_lowercase().contains(&query)
+                || user.email.to_lowercase().contains(&query)
+        })
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|user| user_profile(&user))
+        .collect())
+}
+
+// Number of Registered Users
+#[query]
+fn get_user_count() -> u32 {
+    restore_storage().user_count
+}
+
+// Registration Check
+#[query]
+fn is_registered(id: String) -> bool {
+    restore_storage().users.contains_key(&id)
+}
+
+// Users with a given Role (admin only)
+#[query]
+fn get_users_by_role(admin_id: String, role: Role, offset: u32, limit: u32) -> Result<Vec<UserProfile>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    Ok(state.users.values()
+        .filter(|user| user.role == role)
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|user| user_profile(&user))
+        .collect())
+}
+
+// Users not Logged In Recently (admin only), including those who never have
+#[query]
+fn get_inactive_users(admin_id: String, inactive_since_secs: u64, offset: u32, limit: u32) -> Result<Vec<UserProfile>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let cutoff = now().saturating_sub(inactive_since_secs as i64);
+    Ok(state.users.values()
+        .filter(|user| user.last_login < cutoff)
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|user| user_profile(&user))
+        .collect())
+}
+
+// Bulk User Import (admin only)
+#[update]
+fn import_users(admin_id: String, users: Vec<ImportUser>) -> Result<ImportReport, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let mut report = ImportReport { created: Vec::new(), skipped: Vec::new() };
+    for import in users {
+        if state.users.contains_key(&import.id) || state.email_index.contains_key(&normalize_email(&import.email)) {
+            report.skipped.push(import.id);
+            continue;
+        }
+        state.email_index.insert(normalize_email(&import.email), import.id.clone());
+        let user = new_user(import.id.clone(), import.full_name, import.email, &import.password, import.role, import.preferred_language);
+        state.users.insert(import.id.clone(), user);
+        state.user_count += 1;
+        report.created.push(import.id);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Admin {} imported {} users ({} skipped)", admin_id, report.created.len(), report.skipped.len()))?;
+
+    Ok(report)
+}
+
+// Authenticate User
+#[update]
+fn login_user(id: String, password: String, device_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    match state.users.get_mut(&id) {
+        Some(user) if user.hashed_password == hash_password(&password, &user.salt) => {
+            if user.suspended {
+                return Err(AppError::AccountSuspended);
+            }
+            let session_token = SessionToken {
+                device_id: device_id.clone(),
+                token: generate_id(),
+                expires_at: now() + SESSION_TTL_SECS,
+            };
+            // Logging in again on the same device replaces that device's session only
+            user.sessions.retain(|session| session.device_id != device_id);
+            user.sessions.push(session_token.clone());
+            user.last_login = now();
+
+            // The first login of a day extends or restarts the streak and pays its bonus
+            let today = now() / SECONDS_PER_DAY;
+            if user.last_login_day != today {
+                user.streak = if user.last_login_day == today - 1 { user.streak + 1 } else { 1 };
+                user.last_login_day = today;
+                let bonus = (user.streak * STREAK_BONUS_STEP).min(state.settings.max_streak_bonus);
+                credit_tokens(user, bonus)?;
+                record_token_txn(&mut state.token_history, &id, bonus as i64, &format!("Login streak day {}", user.streak));
+            }
+
+            save_storage(state)?;
+            Ok(session_token.token)
+        }
+        Some(_) => Err(AppError::InvalidCredentials),
+        None => Err(AppError::UserNotFound),
+    }
+}
+
+// Login Streak Retrieval
+#[query]
+fn get_streak(user_id: String) -> u32 {
+    let state = restore_storage();
+    state.users.get(&user_id).map(|user| user.streak).unwrap_or(0)
+}
+
+// Configure the Login Streak Bonus Cap
+#[update]
+fn set_max_streak_bonus(admin_id: String, max_bonus: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.max_streak_bonus = max_bonus;
+
+    save_storage(state)?;
+
+    log_action(&format!("Maximum streak bonus set to {} by {}", max_bonus, admin_id))?;
+
+    Ok("Maximum streak bonus updated".to_string())
+}
+
+// Update a User's Profile; requires a live session. A new email address must
+// be verified again.
+#[update]
+fn update_user(id: String, token: String, full_name: String, email: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    validate_len("full_name", &full_name, MAX_NAME_LEN)?;
+    validate_len("email", &email, MAX_EMAIL_LEN)?;
+
+    let user = state.users.get_mut(&id).ok_or(AppError::UserNotFound)?;
+    check_session_token(user, &token)?;
+
+    if reindex_email(&mut state.email_index, &id, &user.email, &email)? {
+        user.email_verified = false;
+        user.email_verification_token = Some(generate_id());
+    }
+    user.full_name = full_name;
+    user.email = email;
+    award_profile_completion_bonus(user, &state.settings, &mut state.token_history)?;
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} updated their profile", id))?;
+
+    Ok("Profile updated successfully".to_string())
+}
+
+// Change a User's Preferred Language
+#[update]
+fn set_preferred_language(user_id: String, language: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let language = language.to_lowercase();
+    if !state.settings.supported_languages.contains(&language) {
+        return Err(AppError::UnsupportedLanguage);
+    }
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    user.preferred_language = language.clone();
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} set preferred language to {}", user_id, language))?;
+
+    Ok(format!("Preferred language set to {}", language))
+}
+
+// Supported Language Codes
+#[query]
+fn supported_languages() -> Vec<String> {
+    let state = restore_storage();
+    state.settings.supported_languages
+}
+
+// Configure Supported Languages (admin only); English is always kept as the fallback
+#[update]
+fn set_supported_languages(admin_id: String, languages: Vec<String>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let mut languages: Vec<String> = languages.iter().map(|language| language.to_lowercase()).collect();
+    languages.push(DEFAULT_LANGUAGE.to_string());
+    languages.sort();
+    languages.dedup();
+    for language in &languages {
+        validate_len("language", language, MAX_LANGUAGE_LEN)?;
+    }
+    state.settings.supported_languages = languages;
+
+    save_storage(state)?;
+
+    log_action(&format!("Supported languages updated by {}", admin_id))?;
+
+    Ok("Supported languages updated".to_string())
+}
+
+// Verification Token Retrieval (for the mail relay)
+#[query]
+fn get_email_verification_token(admin_id: String, user_id: String) -> Result<Option<String>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    Ok(user.email_verification_token.clone())
+}
+
+// Verify Email
+#[update]
+fn verify_email(user_id: String, token: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    if user.email_verified {
+        return Ok("Email already verified".to_string());
+    }
+    if user.email_verification_token.as_ref() != Some(&token) {
+        return Err(AppError::InvalidVerificationToken);
+    }
+
+    user.email_verified = true;
+    user.email_verification_token = None;
+    advance_onboarding(user, OnboardingStep::EmailVerified, &state.settings, &mut state.token_history, &mut state.notifications)?;
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} verified their email", user_id))?;
+
+    Ok("Email verified successfully".to_string())
+}
+
+// Request a Password Reset. The token goes to the user's email through the
+// mail relay, never back to the caller, and the reply is the same whether or
+// not the email is registered.
+#[update]
+fn request_password_reset(email: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let reply = "If the email is registered, a reset link will be sent".to_string();
+    let Some(user_id) = state.email_index.get(&normalize_email(&email)).cloned() else {
+        return Ok(reply);
+    };
+
+    state.users.get_mut(&user_id).unwrap().password_reset = Some(ResetToken {
+        token: generate_id(),
+        expires_at: now() + PASSWORD_RESET_TTL_SECS,
+    });
+
+    save_storage(state)?;
+
+    log_action(&format!("Password reset requested for user {}", user_id))?;
+
+    Ok(reply)
+}
+
+// Password Reset Token Retrieval (for the mail relay)
+#[query]
+fn get_password_reset_token(admin_id: String, user_id: String) -> Result<Option<String>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    Ok(user.password_reset.as_ref().map(|reset| reset.token.clone()))
+}
+
+// Reset a Password
+#[update]
+fn reset_password(reset_token: String, new_password: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user_id = state.users.values()
+        .find(|user| user.password_reset.as_ref().is_some_and(|reset| reset.token == reset_token))
+        .map(|user| user.id)
+        .ok_or(AppError::InvalidResetToken)?;
+
+    let user = state.users.get_mut(&user_id).unwrap();
+    if user.password_reset.as_ref().unwrap().expires_at < now() {
+        return Err(AppError::ResetTokenExpired);
+    }
+
+    user.salt = generate_id();
+    user.hashed_password = hash_password(&new_password, &user.salt);
+    user.password_reset = None; // Tokens are single use
+    user.sessions.clear();
+
+    save_storage(state)?;
+
+    log_action(&format!("Password reset for user {}", user_id))?;
+
+    Ok("Password reset successfully".to_string())
+}
+
+// Check Session Token
+fn check_session_token(user: &User, token: &str) -> Result<(), AppError> {
+    if user.suspended {
+        return Err(AppError::AccountSuspended);
+    }
+    match user.sessions.iter().find(|session| session.token == token) {
+        Some(session) if session.expires_at >= now() => Ok(()),
+        _ => Err(AppError::SessionTokenExpired),
+    }
+}
+
+// The user behind a live session, for endpoints that act as that user
+fn is_logged_in(users: &Users, user_id: &str, token: &str) -> Result<User, AppError> {
+    let user = users.get(user_id).ok_or(AppError::UserNotFound)?;
+    check_session_token(&user, token)?;
+    Ok(user)
+}
+
+// Check Session Token for read-only queries. A session expired within the
+// grace period is still accepted; the result says whether it is expiring.
+// Updates must use check_session_token, which has no grace period.
+fn check_query_session(user: &User, token: &str, grace_secs: i64) -> Result<bool, AppError> {
+    if user.suspended {
+        return Err(AppError::AccountSuspended);
+    }
+    let current_time = now();
+    match user.sessions.iter().find(|session| session.token == token) {
+        Some(session) if session.expires_at >= current_time => Ok(false),
+        Some(session) if session.expires_at + grace_secs >= current_time => Ok(true),
+        _ => Err(AppError::SessionTokenExpired),
+    }
+}
+
+// Own Profile Retrieval, authenticated by session token
+#[query]
+fn get_my_profile(user_id: String, token: String) -> Result<SessionProfile, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    let session_expiring = check_query_session(&user, &token, state.settings.session_grace_secs)?;
+
+    Ok(SessionProfile { profile: user_profile(&user), session_expiring })
+}
+
+// Configure the Query Session Grace Period (admin only)
+#[update]
+fn set_session_grace_period(admin_id: String, grace_secs: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.session_grace_secs = grace_secs as i64;
+
+    save_storage(state)?;
+
+    log_action(&format!("Session grace period set to {}s by {}", grace_secs, admin_id))?;
+
+    Ok("Session grace period updated".to_string())
+}
+
+// Session Check for the frontend
+#[query]
+fn verify_session(user_id: String, token: String) -> SessionStatus {
+    let state = restore_storage();
+
+    let session = state.users.get(&user_id)
+        .filter(|user| !user.suspended)
+        .and_then(|user| user.sessions.into_iter().find(|session| session.token == token));
+
+    match session {
+        Some(session) if session.expires_at >= now() => SessionStatus::Valid,
+        Some(_) => SessionStatus::Expired,
+        None => SessionStatus::NotFound,
+    }
+}
+
+// Suspend a User (admin only)
+#[update]
+fn suspend_user(admin_id: String, user_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    user.suspended = true;
+    user.sessions.clear(); // End any live sessions immediately
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} suspended by {}", user_id, admin_id))?;
+
+    Ok(format!("User {} suspended", user_id))
+}
+
+// Change a User's Role (admin only)
+#[update]
+fn set_user_role(admin_id: String, user_id: String, role: Role) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    if user.role == Role::Admin && role != Role::Admin {
+        let admin_count = state.users.values().filter(|user| user.role == Role::Admin).count();
+        if admin_count <= 1 {
+            return Err(AppError::LastAdmin);
+        }
+    }
+
+    let previous_role = user.role;
+    let needs_link = role == Role::Admin && user.principal.is_none();
+    state.users.get_mut(&user_id).unwrap().role = role.clone();
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} changed from {:?} to {:?} by {}", user_id, previous_role, role, admin_id))?;
+
+    if needs_link {
+        return Ok(format!("User {} is now Admin and must link a principal before using admin endpoints", user_id));
+    }
+    Ok(format!("User {} is now {:?}", user_id, role))
+}
+
+// Delete a User and their records elsewhere; teams they owned pass to a member (admin only)
+#[update]
+fn delete_user(admin_id: String, user_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    if user.role == Role::Admin && state.users.values().filter(|user| user.role == Role::Admin).count() <= 1 {
+        return Err(AppError::LastAdmin);
+    }
+
+    for title in &user.completed_courses {
+        if let Some(count) = state.course_completions.get_mut(title) {
+            *count = count.saturating_sub(1);
+        }
+    }
+    for team in state.teams.values_mut() {
+        team.members.retain(|member| member != &user_id);
+        team.invited.retain(|invitee| invitee != &user_id);
+        if team.owner == user_id {
+            // The longest-standing remaining member takes the team over
+            team.owner = team.members.first().cloned().unwrap_or_default();
+        }
+    }
+    state.teams.retain(|_, team| !team.members.is_empty());
+    for challenge in state.challenges.values_mut() {
+        challenge.participants.retain(|participant| participant != &user_id);
+        challenge.reminded.retain(|reminded| reminded != &user_id);
+    }
+    for ratings in state.course_ratings.values_mut() {
+        ratings.retain(|(rater, _)| rater != &user_id);
+    }
+    state.course_ratings.retain(|_, ratings| !ratings.is_empty());
+    state.referrals.retain(|referee, referrer| referee != &user_id && referrer != &user_id);
+    if state.email_index.get(&normalize_email(&user.email)) == Some(&user_id) {
+        state.email_index.remove(&normalize_email(&user.email));
+    }
+    state.users.remove(&user_id);
+    state.user_count = state.user_count.saturating_sub(1);
+    state.notifications.remove(&user_id);
+    state.quiz_starts.remove(&user_id);
+    state.quiz_orders.remove(&user_id);
+    state.activity.remove(&user_id);
+    state.quiz_passes.remove(&user_id);
+    state.token_history.remove(&user_id);
+    state.certificates.retain(|_, certificate| certificate.user_id != user_id);
+    state.rank_snapshot.remove(&user_id);
+    state.request_nonces.remove(&user_id);
+    state.transfer_windows.remove(&user_id);
+    state.footprints.remove(&user_id);
+    state.footprint_goals.remove(&user_id);
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} deleted by {}", user_id, admin_id))?;
+
+    Ok(format!("User {} deleted", user_id))
+}
+
+// Reactivate a Suspended User (admin only)
+#[update]
+fn reactivate_user(admin_id: String, user_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    user.suspended = false;
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} reactivated by {}", user_id, admin_id))?;
+
+    Ok(format!("User {} reactivated", user_id))
+}
+
+// Log Out a Single Device; requires a live session
+#[update]
+fn logout(id: String, token: String, device_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user = state.users.get_mut(&id).ok_or(AppError::UserNotFound)?;
+    check_session_token(user, &token)?;
+    user.sessions.retain(|session| session.device_id != device_id);
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} logged out of device {}", id, device_id))?;
+
+    Ok("Logged out successfully".to_string())
+}
+
+// Log Out Every Device; requires a live session
+#[update]
+fn logout_all(id: String, token: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user = state.users.get_mut(&id).ok_or(AppError::UserNotFound)?;
+    check_session_token(user, &token)?;
+    user.sessions.clear();
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} logged out of all devices", id))?;
+
+    Ok("Logged out of all devices".to_string())
+}
+
+// Ensure the caller is an admin: the named account must hold the Admin role
+// and be linked to the principal making the call. A newly promoted admin is
+// told to link one first.
+fn require_admin(users: &Users, admin_id: &str) -> Result<(), AppError> {
+    let caller = authenticated_caller()?;
+    match users.get(admin_id) {
+        Some(user) if user.role == Role::Admin => match user.principal {
+            Some(principal) if principal == caller => Ok(()),
+            None => Err(AppError::PrincipalNotLinked),
+            Some(_) => Err(AppError::Unauthorized),
+        },
+        _ => Err(AppError::Unauthorized),
+    }
+}
+
+// The calling principal; anonymous callers cannot act for any account
+fn authenticated_caller() -> Result<Principal, AppError> {
+    let caller = env::caller();
+    if caller == Principal::anonymous() {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(caller)
+}
+
+// Add a Course (admin only)
+#[update]
+fn add_course(admin_id: String, title: String, levels: HashMap<u32, Quiz>, educational_resources: Vec<String>, prerequisites: Vec<String>, token_cost: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+    
+    require_admin(&state.users, &admin_id)?;
+    if state.courses.contains_key(&title) {
+        return Err(AppError::CourseAlreadyExists);
+    }
+    for quiz in levels.values() {
+        validate_quiz(quiz, &state.settings)?;
+    }
+
+    state.courses.insert(title.clone(), Course { 
+        title: title.clone(), 
+        levels, 
+        educational_resources,
+        prerequisites,
+        token_cost,
+    });
+
+    save_storage(state)?;
+    
+    log_action(&format!("Course {} added by {}", title, admin_id))?;
+    
+    Ok("Course added successfully".to_string())
+}
+
+// Complete a Course
+#[update]
+fn complete_course(user_id: String, title: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+
+    if !course.prerequisites.iter().all(|prerequisite| user.completed_courses.contains(prerequisite)) {
+        return Err(AppError::RequiredCoursesNotCompleted);
+    }
+
+    if !user.completed_courses.contains(&title) {
+        if !user.enrolled_courses.contains(&title) {
+            return Err(AppError::NotEnrolled);
+        }
+        user.enrolled_courses.retain(|enrolled| enrolled != &title);
+        user.completed_courses.push(title.clone());
+        *state.course_completions.entry(title.clone()).or_insert(0) += 1;
+        award_profile_completion_bonus(user, &state.settings, &mut state.token_history)?;
+        advance_onboarding(user, OnboardingStep::CourseCompleted, &state.settings, &mut state.token_history, &mut state.notifications)?;
+        record_activity(&mut state.activity, &user_id, ActivityKind::CourseCompleted, &title);
+        issue_certificate(&mut state.certificates, &user_id, &title);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} completed course {}", user_id, title))?;
+
+    Ok(format!("Course {} completed", title))
+}
+
+// Issue a course completion certificate and return its id
+fn issue_certificate(certificates: &mut Certificates, user_id: &str, course_title: &str) -> String {
+    let id = generate_id();
+    let issued_at = now();
+    let hash = format!("{:x}", Sha256::digest(format!("{}|{}|{}", user_id, course_title, issued_at)));
+    certificates.insert(id.clone(), Certificate {
+        id: id.clone(),
+        user_id: user_id.to_string(),
+        course_title: course_title.to_string(),
+        issued_at,
+        hash,
+    });
+    id
+}
+
+// Certificate Retrieval
+#[query]
+fn get_certificate(cert_id: String) -> Result<Certificate, AppError> {
+    let state = restore_storage();
+    state.certificates.get(&cert_id).cloned().ok_or(AppError::CertificateNotFound)
+}
+
+// Certificates held by a User, oldest first
+#[query]
+fn get_my_certificates(user_id: String) -> Vec<Certificate> {
+    let state = restore_storage();
+    let mut certificates: Vec<Certificate> = state.certificates.values()
+        .filter(|certificate| certificate.user_id == user_id)
+        .cloned()
+        .collect();
+    certificates.sort_by_key(|certificate| certificate.issued_at);
+    certificates
+}
+
+// Certificate Verification against a hash held by a third party
+#[query]
+fn verify_certificate(cert_id: String, expected_hash: String) -> bool {
+    let state = restore_storage();
+    state.certificates.get(&cert_id).is_some_and(|certificate| certificate.hash == expected_hash.to_lowercase())
+}
+
+// Average of a course's ratings
+fn average_course_rating(ratings: &CourseRatings, title: &str) -> Option<f32> {
+    let ratings = ratings.get(title).filter(|ratings| !ratings.is_empty())?;
+    Some(ratings.iter().map(|(_, rating)| *rating as f32).sum::<f32>() / ratings.len() as f32)
+}
+
+fn course_summary(state: &State, title: String, course: &Course) -> CourseSummary {
+    CourseSummary {
+        average_rating: average_course_rating(&state.course_ratings, &title),
+        completions: state.course_completions.get(&title).copied().unwrap_or(0),
+        title,
+        level_count: course.levels.len() as u32,
+        educational_resources: course.educational_resources.clone(),
+        prerequisites: course.prerequisites.clone(),
+        token_cost: course.token_cost,
+    }
+}
+
+// Course Retrieval
+#[query]
+fn get_course(title: String) -> Result<CourseSummary, AppError> {
+    let state = restore_storage();
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    Ok(course_summary(&state, title, &course))
+}
+
+// Number of users who completed a Course
+#[query]
+fn get_course_completion_count(title: String) -> u32 {
+    let state = restore_storage();
+    state.course_completions.get(&title).copied().unwrap_or(0)
+}
+
+// Educational Resources of a Course
+#[query]
+fn get_educational_resources(title: String) -> Result<Vec<String>, AppError> {
+    let state = restore_storage();
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    Ok(course.educational_resources)
+}
+
+// Append an Educational Resource to a Course (admin only)
+#[update]
+fn add_educational_resource(admin_id: String, title: String, resource: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    validate_len("resource", &resource, MAX_RESOURCE_LEN)?;
+
+    let course = state.courses.get_mut(&title).ok_or(AppError::CourseNotFound)?;
+    if course.educational_resources.contains(&resource) {
+        return Err(AppError::ResourceAlreadyExists);
+    }
+    course.educational_resources.push(resource);
+
+    save_storage(state)?;
+
+    log_action(&format!("Resource added to course {} by {}", title, admin_id))?;
+
+    Ok("Educational resource added".to_string())
+}
+
+// Remove an Educational Resource from a Course by index (admin only)
+#[update]
+fn remove_educational_resource(admin_id: String, title: String, index: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let course = state.courses.get_mut(&title).ok_or(AppError::CourseNotFound)?;
+    if index as usize >= course.educational_resources.len() {
+        return Err(AppError::ResourceNotFound);
+    }
+    course.educational_resources.remove(index as usize);
+
+    save_storage(state)?;
+
+    log_action(&format!("Resource {} removed from course {} by {}", index, title, admin_id))?;
+
+    Ok("Educational resource removed".to_string())
+}
+
+// Users who completed a Course (admin only)
+#[query]
+fn get_course_completers(admin_id: String, title: String, offset: u32, limit: u32) -> Result<Vec<UserProfile>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    if !state.courses.contains_key(&title) {
+        return Err(AppError::CourseNotFound);
+    }
+
+    Ok(state.users.values()
+        .filter(|user| user.completed_courses.contains(&title))
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|user| user_profile(&user))
+        .collect())
+}
+
+// List Courses
+#[query]
+fn list_courses() -> Vec<CourseSummary> {
+    let state = restore_storage();
+    state.courses.iter()
+        .map(|(title, course)| course_summary(&state, title, &course))
+        .collect()
+}
+
+// Rate a Course; a user's new rating replaces their previous one
+#[update]
+fn rate_course(user_id: String, title: String, rating: u8) -> Result<f32, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    if !state.courses.contains_key(&title) {
+        return Err(AppError::CourseNotFound);
+    }
+    if !(1..=5).contains(&rating) {
+        return Err(AppError::InvalidRating);
+    }
+
+    let ratings = state.course_ratings.entry(title.clone()).or_default();
+    ratings.retain(|(rater, _)| rater != &user_id);
+    ratings.push((user_id.clone(), rating));
+    let average = average_course_rating(&state.course_ratings, &title).unwrap_or_default();
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} rated course {} with {}", user_id, title, rating))?;
+
+    Ok(average)
+}
+
+// Enroll in a Course
+#[update]
+fn enroll_course(user_id: String, title: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+
+    // Premium courses are paid for once, on first enrollment
+    if course.token_cost > 0 && !user.unlocked_courses.contains(&title) {
+        if user.tokens < course.token_cost {
+            return Err(AppError::InsufficientTokens);
+        }
+        user.tokens -= course.token_cost;
+        user.unlocked_courses.push(title.clone());
+        record_token_txn(&mut state.token_history, &user_id, -(course.token_cost as i64), &format!("Unlocked course {}", title));
+    }
+
+    if !user.enrolled_courses.contains(&title) && !user.completed_courses.contains(&title) {
+        user.enrolled_courses.push(title.clone());
+        record_activity(&mut state.activity, &user_id, ActivityKind::CourseEnrolled, &title);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} enrolled in course {}", user_id, title))?;
+
+    Ok(format!("Enrolled in course {}", title))
+}
+
+// Course Enrollments of a User
+#[query]
+fn list_enrollments(user_id: String) -> Vec<String> {
+    let state = restore_storage();
+    state.users.get(&user_id).map(|user| user.enrolled_courses).unwrap_or_default()
+}
+
+// Title under which a course level's quiz is recorded in passed_quizzes
+fn level_quiz_title(course_title: &str, level: u32) -> String {
+    format!("{} - Level {}", course_title, level)
+}
+
+// Course Progress
+#[query]
+fn get_course_progress(user_id: String, title: String) -> Result<CourseProgress, AppError> {
+    let state = restore_storage();
+
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+
+    let total_levels = course.levels.len() as u32;
+    let passed_levels = course.levels.keys()
+        .filter(|level| user.passed_quizzes.contains(&level_quiz_title(&title, **level)))
+        .count() as u32;
+    let percent_complete = if total_levels == 0 {
+        0.0
+    } else {
+        passed_levels as f32 * 100.0 / total_levels as f32
+    };
+
+    Ok(CourseProgress { total_levels, passed_levels, percent_complete })
+}
+
+// Edit a Course
+#[update]
+fn edit_course(admin_id: String, title: String, levels: HashMap<u32, Quiz>, educational_resources: Vec<String>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    for quiz in levels.values() {
+        validate_quiz(quiz, &state.settings)?;
+    }
+
+    let course = state.courses.get_mut(&title).ok_or(AppError::CourseNotFound)?;
+    course.levels = levels;
+    course.educational_resources = educational_resources;
+
+    save_storage(state)?;
+
+    log_action(&format!("Course {} edited by {}", title, admin_id))?;
+
+    Ok("Course updated successfully".to_string())
+}
+
+// Delete a Course
+#[update]
+fn delete_course(admin_id: String, title: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    if !state.courses.contains_key(&title) {
+        return Err(AppError::CourseNotFound);
+    }
+    if state.challenges.values().any(|challenge| challenge.required_courses.contains(&title)) {
+        return Err(AppError::CourseInUse);
+    }
+
+    state.courses.remove(&title);
+    state.course_ratings.remove(&title);
+    state.course_completions.remove(&title);
+
+    save_storage(state)?;
+
+    log_action(&format!("Course {} deleted by {}", title, admin_id))?;
+
+    Ok("Course deleted successfully".to_string())
+}
+
+// Add a Quiz (admin only)
+#[update]
+#[allow(clippy::too_many_arguments)]
+fn add_quiz(admin_id: String, title: String, level: u32, questions: Vec<String>, options: Vec<Vec<String>>, correct_answers: Vec<String>, reward: u32, time_limit_secs: u64, pass_threshold: u8, cooldown_secs: u64, difficulty: Difficulty) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    if state.quizzes.contains_key(&title) {
+        return Err(AppError::QuizAlreadyExists);
+    }
+    validate_len("title", &title, MAX_NAME_LEN)?;
+
+    let quiz = Quiz {
+        level,
+        questions,
+        options,
+        correct_answers,
+        reward,
+        time_limit_secs,
+        pass_threshold,
+        cooldown_secs,
+        difficulty,
+    };
+    validate_quiz(&quiz, &state.settings)?;
+    state.quizzes.insert(title.clone(), quiz);
+
+    save_storage(state)?;
+
+    log_action(&format!("Quiz {} added by {}", title, admin_id))?;
+
+    Ok("Quiz added successfully".to_string())
+}
+
+// The reward cap, structure and pass threshold every quiz is held to,
+// whether added on its own or as a course level
+fn validate_quiz(quiz: &Quiz, settings: &Settings) -> Result<(), AppError> {
+    if quiz.reward > settings.max_quiz_reward {
+        return Err(AppError::RewardTooLarge);
+    }
+    validate_quiz_structure(&quiz.questions, &quiz.options, &quiz.correct_answers)?;
+    if !(1..=100).contains(&quiz.pass_threshold) {
+        return Err(AppError::InvalidPassThreshold);
+    }
+    validate_quiz_text(&quiz.questions, &quiz.options, &quiz.correct_answers)
+}
+
+// Check that every question has a matching set of options and an answer
+fn validate_quiz_structure(questions: &[String], options: &[Vec<String>], correct_answers: &[String]) -> Result<(), AppError> {
+    if questions.len() != options.len() || questions.len() != correct_answers.len() {
+        return Err(AppError::InvalidQuizStructure);
+    }
+    if options.iter().any(|choices| choices.len() < 2) {
+        return Err(AppError::InvalidQuizStructure);
+    }
+    Ok(())
+}
+
+fn validate_quiz_text(questions: &[String], options: &[Vec<String>], correct_answers: &[String]) -> Result<(), AppError> {
+    for text in questions.iter().chain(options.iter().flatten()).chain(correct_answers.iter()) {
+        validate_len("quiz text", text, MAX_QUIZ_TEXT_LEN)?;
+    }
+    Ok(())
+}
+
+// Edit a Quiz
+#[update]
+fn edit_quiz(admin_id: String, title: String, questions: Vec<String>, options: Vec<Vec<String>>, correct_answers: Vec<String>, reward: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    validate_quiz_structure(&questions, &options, &correct_answers)?;
+    validate_quiz_text(&questions, &options, &correct_answers)?;
+    if reward > state.settings.max_quiz_reward {
+        return Err(AppError::RewardTooLarge);
+    }
+
+    let quiz = state.quizzes.get_mut(&title).ok_or(AppError::QuizNotFound)?;
+    quiz.questions = questions;
+    quiz.options = options;
+    quiz.correct_answers = correct_answers;
+    quiz.reward = reward;
+
+    save_storage(state)?;
+
+    log_action(&format!("Quiz {} edited by {}", title, admin_id))?;
+
+    Ok("Quiz updated successfully".to_string())
+}
+
+// Delete a Quiz
+#[update]
+fn delete_quiz(admin_id: String, title: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    if state.quizzes.remove(&title).is_none() {
+        return Err(AppError::QuizNotFound);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Quiz {} deleted by {}", title, admin_id))?;
+
+    Ok("Quiz deleted successfully".to_string())
+}
+
+// Start a Quiz
+#[update]
+fn start_quiz(user_id: String, quiz_title: String, shuffle: bool) -> Result<QuizSession, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    let quiz = state.quizzes.get(&quiz_title).cloned().ok_or(AppError::QuizNotFound)?;
+
+    // A timed quiz cannot be restarted to reset its clock; one that ran out
+    // without a submission still used up an attempt
+    let previous_start = state.quiz_starts.get(&user_id).and_then(|starts| starts.get(&quiz_title)).copied();
+    if let Some(previous_start) = previous_start.filter(|_| quiz.time_limit_secs > 0) {
+        let deadline = previous_start + quiz.time_limit_secs as i64;
+        if now() <= deadline {
+            return Err(AppError::QuizAlreadyStarted);
+        }
+        count_quiz_attempt(state.users.get_mut(&user_id).unwrap(), &quiz_title, deadline);
+    }
+
+    let started_at = now();
+    state.quiz_starts.entry(user_id.clone()).or_default().insert(quiz_title.clone(), started_at);
+
+    let mut session = QuizSession { started_at, difficulty: quiz.difficulty, questions: quiz.questions.clone(), options: quiz.options.clone() };
+    if shuffle {
+        let seed = format!("{}:{}", user_id, started_at);
+        let order = seeded_permutation(quiz.questions.len(), seed.as_bytes());
+        session.questions = order.iter().map(|index| quiz.questions[*index as usize].clone()).collect();
+        session.options = order.iter()
+            .map(|index| {
+                let choices = &quiz.options[*index as usize];
+                seeded_permutation(choices.len(), format!("{}:{}", seed, index).as_bytes())
+                    .into_iter()
+                    .map(|choice| choices[choice as usize].clone())
+                    .collect()
+            })
+            .collect();
+        state.quiz_orders.entry(user_id.clone()).or_default().insert(quiz_title.clone(), order);
+    } else if let Some(orders) = state.quiz_orders.get_mut(&user_id) {
+        orders.remove(&quiz_title);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} started quiz {}", user_id, quiz_title))?;
+
+    Ok(session)
+}
+
+// Fisher-Yates shuffle of 0..len driven by a hash of the seed, so the same
+// seed always yields the same order
+fn seeded_permutation(len: usize, seed: &[u8]) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..len as u32).collect();
+    for i in (1..len).rev() {
+        let digest = Sha256::new().chain_update(seed).chain_update((i as u64).to_be_bytes()).finalize();
+        let random = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        order.swap(i, (random % (i as u64 + 1)) as usize);
+    }
+    order
+}
+
+// Dry-run Check of Quiz Answers. Answers are matched against the quiz's
+// canonical question order.
+#[query]
+fn validate_quiz_answers(quiz_title: String, answers: Vec<String>) -> Result<ValidationReport, AppError> {
+    let state = restore_storage();
+
+    let quiz = state.quizzes.get(&quiz_title).ok_or(AppError::QuizNotFound)?;
+    Ok(ValidationReport {
+        expected_count: quiz.questions.len() as u32,
+        count_matches: answers.len() == quiz.questions.len(),
+        valid_answers: answers.iter()
+            .enumerate()
+            .map(|(index, answer)| quiz.options.get(index).is_some_and(|choices| choices.contains(answer)))
+            .collect(),
+    })
+}
+
+// Submit Quiz Answers
+#[update]
+fn submit_quiz(user_id: String, quiz_title: String, answers: Vec<String>) -> Result<QuizResult, AppError> {
+    let mut state = restore_storage();
+
+    let quiz = state.quizzes.get(&quiz_title).cloned().ok_or(AppError::QuizNotFound)?;
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+
+    // Timed quizzes must be submitted within the limit of a recorded start
+    let started_at = state.quiz_starts.get_mut(&user_id).and_then(|starts| starts.remove(&quiz_title));
+    if quiz.time_limit_secs > 0 {
+        let started_at = started_at.ok_or(AppError::QuizNotStarted)?;
+        if now() - started_at > quiz.time_limit_secs as i64 {
+            // The late submission still counts as an attempt
+            count_quiz_attempt(state.users.get_mut(&user_id).unwrap(), &quiz_title, now());
+            save_storage(state)?;
+            log_action(&format!("User {} submitted quiz {} after its time limit", user_id, quiz_title))?;
+            return Err(AppError::QuizTimedOut);
+        }
+    }
+
+    let user = state.users.get_mut(&user_id).unwrap();
+    check_quiz_cooldown(user, &quiz_title, &quiz)?;
+    let already_passed = user.passed_quizzes.contains(&quiz_title);
+    let attempts = user.quiz_attempts.get(&quiz_title).copied().unwrap_or(0);
+    if attempts >= state.settings.max_quiz_attempts && !already_passed {
+        return Err(AppError::MaxAttemptsReached);
+    }
+    count_quiz_attempt(user, &quiz_title, now());
+
+    // Shuffled attempts are answered in the shown order; grade in canonical order
+    let order = state.quiz_orders.get_mut(&user_id).and_then(|orders| orders.remove(&quiz_title));
+    let mut result = match &order {
+        Some(order) => {
+            let mut canonical = vec![String::new(); quiz.questions.len()];
+            for (position, answer) in answers.into_iter().enumerate() {
+                if let Some(index) = order.get(position) {
+                    canonical[*index as usize] = answer;
+                }
+            }
+            let mut result = grade_quiz(&quiz, &canonical);
+            result.per_question = order.iter().map(|index| result.per_question[*index as usize]).collect();
+            result
+        }
+        None => grade_quiz(&quiz, &answers),
+    };
+    result.already_passed = already_passed;
+
+    if result.passed && !already_passed {
+        let reward = quiz_reward(&state.settings, &quiz);
+        user.passed_quizzes.push(quiz_title.clone());
+        credit_tokens(user, reward)?;
+        record_quiz_pass(&mut state.quiz_passes, &user_id, &quiz_title, &result);
+        advance_onboarding(user, OnboardingStep::QuizPassed, &state.settings, &mut state.token_history, &mut state.notifications)?;
+        record_token_txn(&mut state.token_history, &user_id, reward as i64, &format!("Passed quiz {}", quiz_title));
+        record_activity(&mut state.activity, &user_id, ActivityKind::QuizPassed, &quiz_title);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} submitted quiz {} ({}/{})", user_id, quiz_title, result.score, result.total))?;
+
+    Ok(result)
+}
+
+// Pass a Quiz as a logged-in user. The answers are graded as by submit_quiz,
+// so the reward is only credited for a passing submission.
+#[update]
+fn pass_quiz(user_id: String, token: String, quiz_title: String, answers: Vec<String>) -> Result<QuizResult, AppError> {
+    is_logged_in(&restore_storage().users, &user_id, &token)?;
+    submit_quiz(user_id, quiz_title, answers)
+}
+
+fn count_quiz_attempt(user: &mut User, quiz_title: &str, at: i64) {
+    *user.quiz_attempts.entry(quiz_title.to_string()).or_insert(0) += 1;
+    user.last_quiz_attempts.insert(quiz_title.to_string(), at);
+}
+
+// Reject an attempt made sooner than the quiz's cooldown allows
+fn check_quiz_cooldown(user: &User, quiz_title: &str, quiz: &Quiz) -> Result<(), AppError> {
+    if let Some(last_attempt) = user.last_quiz_attempts.get(quiz_title) {
+        let elapsed = now() - last_attempt;
+        if elapsed < quiz.cooldown_secs as i64 {
+            return Err(AppError::QuizOnCooldown(quiz.cooldown_secs - elapsed as u64));
+        }
+    }
+    Ok(())
+}
+
+// Score answers against a quiz's answer key
+fn grade_quiz(quiz: &Quiz, answers: &[String]) -> QuizResult {
+    let total = quiz.questions.len() as u32;
+    // Missing answers count as incorrect
+    let per_question: Vec<bool> = quiz.correct_answers.iter()
+        .enumerate()
+        .map(|(index, expected)| answers.get(index) == Some(expected))
+        .collect();
+    let score = per_question.iter().filter(|correct| **correct).count() as u32;
+    let passed = total > 0 && score * 100 >= total * quiz.pass_threshold as u32;
+
+    QuizResult { score, total, passed, per_question, already_passed: false }
+}
+
+// Submit every level quiz of a course at once
+#[update]
+fn submit_course_quizzes(user_id: String, title: String, answers_by_level: HashMap<u32, Vec<String>>) -> Result<CourseResult, AppError> {
+    let mut state = restore_storage();
+
+    let course = state.courses.get(&title).ok_or(AppError::CourseNotFound)?;
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+
+    let mut levels: Vec<(&u32, &Quiz)> = course.levels.iter().collect();
+    levels.sort_by_key(|(level, _)| **level);
+
+    // Check attempts up front so a rejected batch records nothing
+    for (level, quiz) in &levels {
+        let quiz_title = level_quiz_title(&title, **level);
+        if answers_by_level.contains_key(level) {
+            check_quiz_cooldown(user, &quiz_title, quiz)?;
+        }
+        let attempts = user.quiz_attempts.get(&quiz_title).copied().unwrap_or(0);
+        if answers_by_level.contains_key(level) && attempts >= state.settings.max_quiz_attempts && !user.passed_quizzes.contains(&quiz_title) {
+            return Err(AppError::MaxAttemptsReached);
+        }
+    }
+
+    let mut level_results = Vec::new();
+    let mut earned = Vec::new();
+    for (level, quiz) in levels {
+        let Some(answers) = answers_by_level.get(level) else {
+            continue;
+        };
+        let quiz_title = level_quiz_title(&title, *level);
+        *user.quiz_attempts.entry(quiz_title.clone()).or_insert(0) += 1;
+        user.last_quiz_attempts.insert(quiz_title.clone(), now());
+
+        let mut result = grade_quiz(quiz, answers);
+        result.already_passed = user.passed_quizzes.contains(&quiz_title);
+        if result.passed && !result.already_passed {
+            let reward = quiz_reward(&state.settings, quiz);
+            user.passed_quizzes.push(quiz_title.clone());
+            credit_tokens(user, reward)?;
+            record_quiz_pass(&mut state.quiz_passes, &user_id, &quiz_title, &result);
+            advance_onboarding(user, OnboardingStep::QuizPassed, &state.settings, &mut state.token_history, &mut state.notifications)?;
+            earned.push((reward, quiz_title));
+        }
+        level_results.push((*level, result));
+    }
+
+    let all_passed = course.levels.keys().all(|level| user.passed_quizzes.contains(&level_quiz_title(&title, *level)));
+    let prerequisites_met = course.prerequisites.iter().all(|prerequisite| user.completed_courses.contains(prerequisite));
+    let already_completed = user.completed_courses.contains(&title);
+    let enrolled = user.enrolled_courses.contains(&title);
+    let course_completed = already_completed || (all_passed && prerequisites_met && enrolled);
+    if course_completed && !already_completed {
+        user.enrolled_courses.retain(|enrolled| enrolled != &title);
+        user.completed_courses.push(title.clone());
+        *state.course_completions.entry(title.clone()).or_insert(0) += 1;
+        award_profile_completion_bonus(user, &state.settings, &mut state.token_history)?;
+        advance_onboarding(user, OnboardingStep::CourseCompleted, &state.settings, &mut state.token_history, &mut state.notifications)?;
+        record_activity(&mut state.activity, &user_id, ActivityKind::CourseCompleted, &title);
+        issue_certificate(&mut state.certificates, &user_id, &title);
+    }
+
+    for (reward, quiz_title) in earned {
+        record_token_txn(&mut state.token_history, &user_id, reward as i64, &format!("Passed quiz {}", quiz_title));
+        record_activity(&mut state.activity, &user_id, ActivityKind::QuizPassed, &quiz_title);
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} submitted {} level quizzes of course {}", user_id, level_results.len(), title))?;
+
+    Ok(CourseResult { level_results, course_completed })
+}
+
+// Next Onboarding Step for a User
+#[query]
+fn get_next_recommended_action(user_id: String) -> Result<RecommendedAction, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    let action = |kind, target: Option<String>, message: String| RecommendedAction { kind, target, message };
+
+    if !user.email_verified {
+        return Ok(action(ActionKind::VerifyEmail, None, "Verify your email address".to_string()));
+    }
+
+    // Work through enrolled courses level by level
+    for title in &user.enrolled_courses {
+        let Some(course) = state.courses.get(title) else {
+            continue;
+        };
+        let mut levels: Vec<&u32> = course.levels.keys().collect();
+        levels.sort();
+        if let Some(level) = levels.into_iter().find(|level| !user.passed_quizzes.contains(&level_quiz_title(title, **level))) {
+            let quiz_title = level_quiz_title(title, *level);
+            return Ok(action(ActionKind::CompleteQuiz, Some(quiz_title.clone()), format!("Complete quiz {}", quiz_title)));
+        }
+        return Ok(action(ActionKind::CompleteCourse, Some(title.clone()), format!("Complete course {}", title)));
+    }
+
+    if user.completed_courses.is_empty() {
+        let available = state.courses.iter().find(|(_, course)| {
+            course.prerequisites.iter().all(|prerequisite| user.completed_courses.contains(prerequisite))
+        });
+        if let Some((title, _)) = available {
+            return Ok(action(ActionKind::EnrollInCourse, Some(title.clone()), format!("Enroll in course {}", title)));
+        }
+    }
+
+    let mut challenges: Vec<(&String, &Challenge)> = state.challenges.iter().collect();
+    challenges.sort_by_key(|(id, _)| *id);
+    let open_challenge = challenges.into_iter().find(|(id, challenge)| {
+        !user.challenges_completed.contains(id)
+            && challenge.ends_at.is_none_or(|ends_at| now() < ends_at)
+            && meets_challenge_requirements(&user, challenge)
+    });
+    if let Some((id, challenge)) = open_challenge {
+        return Ok(action(ActionKind::ParticipateInChallenge, Some(id.clone()), format!("Participate in challenge: {}", challenge.description)));
+    }
+
+    Ok(action(ActionKind::Done, None, "You're all caught up".to_string()))
+}
+
+// Submit Feedback
+#[update]
+fn submit_feedback(user_id: String, feedback: String, category: FeedbackCategory, rating: Option<u8>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    validate_len("feedback", &feedback, MAX_FEEDBACK_LEN)?;
+    if rating.is_some_and(|rating| !(1..=5).contains(&rating)) {
+        return Err(AppError::InvalidRating);
+    }
+
+    state.feedbacks.push(Feedback {
+        id: generate_id(),
+        user_id: user_id.clone(),
+        feedback,
+        status: FeedbackStatus::New,
+        response: None,
+        category,
+        rating,
+    });
+    
+    save_storage(state)?;
+    
+    log_action(&format!("Feedback submitted by user {}", user_id))?;
+    
+    Ok("Feedback submitted successfully".to_string())
+}
+
+// Feedback by Status (admin only, newest first)
+#[query]
+fn get_feedbacks_by_status(admin_id: String, status: FeedbackStatus, offset: u32, limit: u32) -> Result<Vec<Feedback>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    Ok(state.feedbacks.iter()
+        .rev()
+        .filter(|feedback| feedback.status == status)
+        .skip(offset as usize)
+        .take(limit as usize)
+        .cloned()
+        .collect())
+}
+
+// Feedback Statistics (admin only)
+#[query]
+fn get_feedback_stats(admin_id: String) -> Result<FeedbackStats, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let counts_by_category = [FeedbackCategory::Bug, FeedbackCategory::Feature, FeedbackCategory::Content, FeedbackCategory::Other]
+        .into_iter()
+        .map(|category| {
+            let count = state.feedbacks.iter().filter(|feedback| feedback.category == category).count() as u32;
+            (category, count)
+        })
+        .collect();
+
+    let ratings: Vec<u8> = state.feedbacks.iter().filter_map(|feedback| feedback.rating).collect();
+    let average_rating = if ratings.is_empty() {
+        None
+    } else {
+        Some(ratings.iter().map(|rating| *rating as f32).sum::<f32>() / ratings.len() as f32)
+    };
+
+    Ok(FeedbackStats { counts_by_category, average_rating })
+}
+
+// Respond to Feedback (admin only)
+#[update]
+fn respond_to_feedback(admin_id: String, feedback_id: String, response: String, status: FeedbackStatus) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let feedback = state.feedbacks.iter_mut()
+        .find(|feedback| feedback.id == feedback_id)
+        .ok_or(AppError::FeedbackNotFound)?;
+    feedback.response = Some(response.clone());
+    feedback.status = status;
+    let author_id = feedback.user_id.clone();
+
+    // The author may have been deleted since submitting
+    if let Some(author) = state.users.get(&author_id) {
+        push_notification(&mut state.notifications, &author_id, state.settings.max_notifications, Notification {
+            user_id: author_id.clone(),
+            message: render_notification(NotificationType::FeedbackResponse, &author.preferred_language, &[&response]),
+            timestamp: env::time() as i64,
+            notification_type: NotificationType::FeedbackResponse,
+            read: false,
+        });
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Feedback {} answered by {}", feedback_id, admin_id))?;
+
+    Ok("Feedback response posted".to_string())
+}
+
+// Add a Challenge (admin only)
+#[update]
+#[allow(clippy::too_many_arguments)]
+fn add_challenge(admin_id: String, description: String, reward_tokens: u32, required_courses: Vec<String>, required_quizzes: Vec<String>, team_challenge: bool, recurrence_secs: Option<u64>, category: String, ends_at: Option<i64>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    if reward_tokens > state.settings.max_challenge_reward {
+        return Err(AppError::RewardTooLarge);
+    }
+
+    let id = generate_id();
+    state.challenges.insert(id.clone(), Challenge { 
+        description, 
+        reward_tokens, 
+        required_courses, 
+        required_quizzes,
+        participants: Vec::new(), 
+        team_challenge,
+        recurrence_secs,
+        category,
+        ends_at,
+        reminded: Vec::new(),
+    });
+
+    save_storage(state)?;
+    
+    log_action(&format!("Challenge {} added by {}", id, admin_id))?;
+    
+    Ok("Challenge added successfully".to_string())
+}
+
+// Edit a Challenge (admin only); omitted fields and participants are kept
+#[update]
+fn edit_challenge(admin_id: String, challenge_id: String, description: Option<String>, reward_tokens: Option<u32>, required_courses: Option<Vec<String>>, required_quizzes: Option<Vec<String>>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    if reward_tokens.is_some_and(|reward_tokens| reward_tokens > state.settings.max_challenge_reward) {
+        return Err(AppError::RewardTooLarge);
+    }
+    let challenge = state.challenges.get_mut(&challenge_id).ok_or(AppError::ChallengeNotFound)?;
+
+    if let Some(description) = description {
+        challenge.description = description;
+    }
+    if let Some(reward_tokens) = reward_tokens {
+        challenge.reward_tokens = reward_tokens;
+    }
+    if let Some(required_courses) = required_courses {
+        challenge.required_courses = required_courses;
+    }
+    if let Some(required_quizzes) = required_quizzes {
+        challenge.required_quizzes = required_quizzes;
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Challenge {} edited by {}", challenge_id, admin_id))?;
+
+    Ok("Challenge updated successfully".to_string())
+}
+
+// Build the public summary of a challenge
+fn challenge_summary(id: &str, challenge: &Challenge) -> ChallengeSummary {
+    ChallengeSummary {
+        id: id.to_string(),
+        description: challenge.description.clone(),
+        category: challenge.category.clone(),
+        reward_tokens: challenge.reward_tokens,
+        participant_count: challenge.participants.len() as u32,
+    }
+}
+
+// List Challenges in a Category
+#[query]
+fn list_challenges_by_category(category: String) -> Vec<ChallengeSummary> {
+    let state = restore_storage();
+    state.challenges.iter()
+        .filter(|(_, challenge)| challenge.category == category)
+        .map(|(id, challenge)| challenge_summary(id, challenge))
+        .collect()
+}
+
+// List Challenge Categories
+#[query]
+fn list_categories() -> Vec<String> {
+    let state = restore_storage();
+    let mut categories: Vec<String> = state.challenges.values()
+        .map(|challenge| challenge.category.clone())
+        .collect();
+    categories.sort();
+    categories.dedup();
+    categories
+}
+
+// Challenge Participants
+#[query]
+fn get_challenge_participants(challenge_id: String, offset: u32, limit: u32) -> Result<Vec<UserProfile>, AppError> {
+    let state = restore_storage();
+
+    let challenge = state.challenges.get(&challenge_id).ok_or(AppError::ChallengeNotFound)?;
+
+    Ok(challenge.participants.iter()
+        .filter_map(|participant| state.users.get(participant)) // Skip deleted accounts
+        .skip(offset as usize)
+        .take(limit as usize)
+        .map(|user| user_profile(&user))
+        .collect())
+}
+
+// Challenges a User has taken part in
+#[query]
+fn get_my_challenges(user_id: String) -> Result<Vec<ChallengeSummary>, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+
+    Ok(user.challenges_completed.iter()
+        .filter_map(|id| state.challenges.get(id).map(|challenge| challenge_summary(id, challenge)))
+        .collect())
+}
+
+// Number of Users who currently qualify for a Challenge
+#[query]
+fn get_eligible_user_count(challenge_id: String) -> Result<u32, AppError> {
+    let state = restore_storage();
+
+    let challenge = state.challenges.get(&challenge_id).ok_or(AppError::ChallengeNotFound)?;
+
+    Ok(state.users.values()
+        .filter(|user| meets_challenge_requirements(user, challenge))
+        .count() as u32)
+}
+
+// Check whether a user has completed a challenge's required courses and quizzes
+fn meets_challenge_requirements(user: &User, challenge: &Challenge) -> bool {
+    challenge.required_courses.iter().all(|course| user.completed_courses.contains(course))
+        && challenge.required_quizzes.iter().all(|quiz| user.passed_quizzes.contains(quiz))
+}
+
+// Challenge Participation
+#[update]
+fn participate_in_challenge(user_id: String, challenge_id: String) -> Result<String, AppError> {
+    join_challenge(user_id, challenge_id, false)
+}
+
+// Challenge Participation for testing. With admin_bypass set, an admin skips
+// the course/quiz prerequisites; everyone else is checked as usual.
+#[update]
+fn admin_participate_in_challenge(user_id: String, challenge_id: String, admin_bypass: bool) -> Result<String, AppError> {
+    join_challenge(user_id, challenge_id, admin_bypass)
+}
+
+fn join_challenge(user_id: String, challenge_id: String, admin_bypass: bool) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let challenge = state.challenges.get(&challenge_id).cloned().ok_or(AppError::ChallengeNotFound)?;
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    if challenge.ends_at.is_some_and(|ends_at| now() >= ends_at) {
+        return Err(AppError::ChallengeEnded);
+    }
+    let bypass = admin_bypass && require_admin(&state.users, &user_id).is_ok();
+
+    // Team challenges are completed by every member of the participant's team
+    let members = if challenge.team_challenge {
+        state.teams.values()
+            .find(|team| team.members.contains(&user_id))
+            .map(|team| team.members.clone())
+            .ok_or(AppError::TeamNotFound)?
+    } else {
+        vec![user_id.clone()]
+    };
+
+    for member in &members {
+        let user = state.users.get(member).ok_or(AppError::UserNotFound)?;
+        let skip_requirements = bypass && member == &user_id; // Team members are still checked
+        if !skip_requirements && !meets_challenge_requirements(&user, &challenge) {
+            return Err(AppError::RequiredCoursesNotCompleted);
+        }
+        // Only recurring challenges can be completed more than once
+        let participated = user.challenges_completed.contains(&challenge_id) || challenge.participants.contains(member);
+        if participated && challenge.recurrence_secs.is_none() {
+            return Err(AppError::AlreadyParticipated);
+        }
+        if let (Some(interval), Some(last)) = (challenge.recurrence_secs, user.last_challenge_participation.get(&challenge_id)) {
+            if now() - last < interval as i64 {
+                return Err(AppError::ChallengeNotAvailableYet);
+            }
+        }
+    }
+
+    let reward_tokens = boosted_reward(&state.settings, challenge.reward_tokens);
+    let share = reward_tokens / members.len() as u32;
+    let remainder = reward_tokens % members.len() as u32;
+
+    for member in &members {
+        let reward = if member == &user_id { share + remainder } else { share };
+        let user = state.users.get_mut(member).unwrap();
+        user.challenges_completed.retain(|id| id != &challenge_id); // A repeated challenge is listed once
+        user.challenges_completed.push(challenge_id.clone());
+        user.last_challenge_participation.insert(challenge_id.clone(), now());
+        credit_tokens(user, reward)?;
+        record_token_txn(&mut state.token_history, member, reward as i64, &format!("Completed challenge {}", challenge_id));
+        check_milestones(user, &state.settings, &mut state.token_history)?;
+        advance_onboarding(user, OnboardingStep::ChallengeJoined, &state.settings, &mut state.token_history, &mut state.notifications)?;
+        record_activity(&mut state.activity, member, ActivityKind::ChallengeJoined, &challenge_id);
+
+        push_notification(&mut state.notifications, member, state.settings.max_notifications, Notification {
+            user_id: member.clone(),
+            message: render_notification(NotificationType::ChallengeParticipated, &user.preferred_language, &[&challenge.description]),
+            timestamp: env::time() as i64,
+            notification_type: NotificationType::ChallengeParticipated,
+            read: false,
+        });
+    }
+
+    let participants = &mut state.challenges.get_mut(&challenge_id).unwrap().participants;
+    for member in members {
+        if !participants.contains(&member) {
+            participants.push(member);
+        }
+    }
+
+    save_storage(state)?;
+
+    if bypass {
+        log_action(&format!("Admin {} participated in challenge {} bypassing prerequisites", user_id, challenge_id))?;
+    } else {
+        log_action(&format!("User {} participated in challenge {}", user_id, challenge_id))?;
+    }
+
+    Ok(format!("Successfully participated in challenge: {}", challenge.description))
+}
+
+// Remind non-participants of challenges nearing their deadline. Meant to be
+// called periodically by an off-chain timer.
+#[update]
+fn send_challenge_reminders(admin_id: String) -> Result<u32, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let current_time = now();
+    let users: Vec<(String, String)> = state.users.iter().map(|(id, user)| (id, user.preferred_language)).collect();
+    let mut sent = 0;
+
+    // Reminders are buffered and written by the single save below
+    for challenge in state.challenges.values_mut() {
+        let Some(ends_at) = challenge.ends_at else {
+            continue;
+        };
+        if current_time >= ends_at || ends_at - current_time > CHALLENGE_REMINDER_WINDOW_SECS {
+            continue;
+        }
+
+        for (user_id, lang) in &users {
+            if challenge.participants.contains(user_id) || challenge.reminded.contains(user_id) {
+                continue;
+            }
+            push_notification(&mut state.notifications, user_id, state.settings.max_notifications, Notification {
+                user_id: user_id.clone(),
+                message: render_notification(NotificationType::ChallengeReminder, lang, &[&challenge.description]),
+                timestamp: env::time() as i64,
+                notification_type: NotificationType::ChallengeReminder,
+                read: false,
+            });
+            challenge.reminded.push(user_id.clone());
+            sent += 1;
+        }
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("{} challenge reminders sent", sent))?;
+
+    Ok(sent)
+}
+
+// Create a Team owned by the calling user; requires a live session. Other
+// users are invited and join once they accept.
+#[update]
+fn create_team(owner_id: String, token: String, name: String, invitees: Vec<String>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let owner = state.users.get(&owner_id).ok_or(AppError::UserNotFound)?;
+    check_session_token(&owner, &token)?;
+    validate_len("name", &name, MAX_NAME_LEN)?;
+    if invitees.iter().any(|invitee| !state.users.contains_key(invitee)) {
+        return Err(AppError::UserNotFound);
+    }
+
+    let id = generate_id();
+    let mut team = Team { id: id.clone(), name, members: vec![owner_id.clone()], owner: owner_id.clone(), invited: Vec::new() };
+    invite_to_team(&mut team, invitees);
+    state.teams.insert(id.clone(), team);
+
+    save_storage(state)?;
+
+    log_action(&format!("Team {} created by {}", id, owner_id))?;
+
+    Ok(id)
+}
+
+// Queue invitations for users not already in or invited to the team
+fn invite_to_team(team: &mut Team, invitees: Vec<String>) {
+    for invitee in invitees {
+        if !team.members.contains(&invitee) && !team.invited.contains(&invitee) {
+            team.invited.push(invitee);
+        }
+    }
+}
+
+// Team changes are made by the owner with a live session, or by an admin
+fn require_team_manager(users: &Users, team: &Team, user_id: &str, token: &str) -> Result<(), AppError> {
+    if require_admin(users, user_id).is_ok() {
+        return Ok(());
+    }
+    let user = users.get(user_id).ok_or(AppError::UserNotFound)?;
+    check_session_token(&user, token)?;
+    if team.owner != user_id {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(())
+}
+
+// Team Retrieval
+#[query]
+fn get_team(team_id: String) -> Option<Team> {
+    let state = restore_storage();
+    state.teams.get(&team_id).cloned()
+}
+
+// Update a Team (owner or admin). Members left out of the list are removed,
+// except the owner; users new to the list are invited rather than added.
+#[update]
+fn update_team(user_id: String, token: String, team_id: String, name: String, members: Vec<String>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let team = state.teams.get(&team_id).ok_or(AppError::TeamNotFound)?;
+    require_team_manager(&state.users, team, &user_id, &token)?;
+    validate_len("name", &name, MAX_NAME_LEN)?;
+    if members.iter().any(|member| !state.users.contains_key(member)) {
+        return Err(AppError::UserNotFound);
+    }
+
+    let team = state.teams.get_mut(&team_id).unwrap();
+    team.name = name;
+    let owner = team.owner.clone();
+    team.members.retain(|member| member == &owner || members.contains(member));
+    team.invited.retain(|invitee| members.contains(invitee));
+    invite_to_team(team, members);
+
+    save_storage(state)?;
+
+    log_action(&format!("Team {} updated by {}", team_id, user_id))?;
+
+    Ok("Team updated successfully".to_string())
+}
+
+// Accept an Invitation to a Team; requires a live session
+#[update]
+fn accept_team_invite(user_id: String, token: String, team_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    check_session_token(&user, &token)?;
+    let team = state.teams.get_mut(&team_id).ok_or(AppError::TeamNotFound)?;
+    if !team.invited.contains(&user_id) {
+        return Err(AppError::NotInvited);
+    }
+    team.invited.retain(|invitee| invitee != &user_id);
+    team.members.push(user_id.clone());
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} joined team {}", user_id, team_id))?;
+
+    Ok("Joined team successfully".to_string())
+}
+
+// Delete a Team (owner or admin)
 #[update]
-fn add_course(title: String, levels: HashMap<u32, Quiz>, educational_resources: Vec<String>) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, mut courses, _, _, _) = restore_storage();
+fn delete_team(user_id: String, token: String, team_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let team = state.teams.get(&team_id).ok_or(AppError::TeamNotFound)?;
+    require_team_manager(&state.users, team, &user_id, &token)?;
+    state.teams.remove(&team_id);
+
+    save_storage(state)?;
+
+    log_action(&format!("Team {} deleted by {}", team_id, user_id))?;
+
+    Ok("Team deleted successfully".to_string())
+}
+
+// Submit a Social Notification
+#[update]
+fn send_notification(from_user: String, to_user: String, message: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&from_user) || !state.users.contains_key(&to_user) {
+        return Err(AppError::UserNotFound);
+    }
+    validate_len("message", &message, MAX_MESSAGE_LEN)?;
+
+    let recipient = state.users.get(&to_user).unwrap();
+    let notification = Notification {
+        user_id: to_user.clone(),
+        message: render_notification(NotificationType::AchievementShared, &recipient.preferred_language, &[&from_user, &message]),
+        timestamp: env::time() as i64,
+        notification_type: NotificationType::AchievementShared,
+        read: false,
+    };
+
+    push_notification(&mut state.notifications, &to_user, state.settings.max_notifications, notification);
+
+    save_storage(state)?;
+
+    log_action(&format!("Notification sent from {} to {}", from_user, to_user))?;
     
-    if courses.contains_key(&title) {
-        return Err(AppError::CourseAlreadyExists);
+    Ok("Notification sent successfully".to_string())
+}
+
+// Broadcast an Announcement to every user (admin only). All notifications are
+// built in the buffered map and written by the single save at the end, so a
+// failure part way leaves stable memory untouched.
+#[update]
+fn broadcast_notification(admin_id: String, message: String) -> Result<u32, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    validate_len("message", &message, MAX_MESSAGE_LEN)?;
+
+    let timestamp = env::time() as i64;
+    let mut sent = 0;
+    for (user_id, user) in state.users.iter() {
+        push_notification(&mut state.notifications, &user_id, state.settings.max_notifications, Notification {
+            user_id: user_id.clone(),
+            message: render_notification(NotificationType::Announcement, &user.preferred_language, &[&message]),
+            timestamp,
+            notification_type: NotificationType::Announcement,
+            read: false,
+        });
+        sent += 1;
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Admin {} broadcast an announcement to {} users", admin_id, sent))?;
+
+    Ok(sent)
+}
+
+// Share an Achievement with another User
+#[update]
+fn share_achievement(user_id: String, achievement_id: String, to_user: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let sharer = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    let recipient = state.users.get(&to_user).ok_or(AppError::UserNotFound)?;
+    if !sharer.achievements.contains(&achievement_id) {
+        return Err(AppError::AchievementNotOwned);
+    }
+
+    push_notification(&mut state.notifications, &to_user, state.settings.max_notifications, Notification {
+        user_id: to_user.clone(),
+        message: render_notification(NotificationType::AchievementShared, &recipient.preferred_language, &[&user_id, &achievement_id]),
+        timestamp: env::time() as i64,
+        notification_type: NotificationType::AchievementShared,
+        read: false,
+    });
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} shared achievement {} with {}", user_id, achievement_id, to_user))?;
+
+    Ok("Achievement shared successfully".to_string())
+}
+
+// Nearest Reward a User cannot yet afford
+#[query]
+fn get_nearest_affordable_reward(user_id: String) -> Result<NearestReward, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+
+    let nearest = state.rewards.values()
+        .filter(|reward| reward.cost_tokens > user.tokens)
+        .min_by_key(|reward| (reward.cost_tokens, reward.id.clone()));
+
+    Ok(match nearest {
+        Some(reward) => NearestReward {
+            tokens_needed: reward.cost_tokens - user.tokens,
+            reward: Some(reward.clone()),
+            all_affordable: false,
+        },
+        None => NearestReward { reward: None, tokens_needed: 0, all_affordable: true },
+    })
+}
+
+// Add a Reward
+#[update]
+fn add_reward(admin_id: String, description: String, cost_tokens: u32, category: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let id = generate_id();
+    state.rewards.insert(id.clone(), Reward { id: id.clone(), description, cost_tokens, category });
+
+    save_storage(state)?;
+
+    log_action(&format!("Reward {} added by {}", id, admin_id))?;
+
+    Ok(id)
+}
+
+// List Rewards in a Category
+#[query]
+fn list_rewards_by_category(category: String) -> Vec<Reward> {
+    let state = restore_storage();
+    state.rewards.values()
+        .filter(|reward| reward.category == category)
+        .cloned()
+        .collect()
+}
+
+// List Reward Categories
+#[query]
+fn list_reward_categories() -> Vec<String> {
+    let state = restore_storage();
+    let mut categories: Vec<String> = state.rewards.values()
+        .map(|reward| reward.category.clone())
+        .collect();
+    categories.sort();
+    categories.dedup();
+    categories
+}
+
+// List Rewards with what a User can afford
+#[query]
+fn list_rewards_for_user(user_id: String) -> Result<Vec<RewardWithAffordability>, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+
+    Ok(state.rewards.values()
+        .map(|reward| RewardWithAffordability {
+            reward: reward.clone(),
+            can_afford: user.tokens >= reward.cost_tokens,
+        })
+        .collect())
+}
+
+// Reject a replayed sensitive update. Nonces are optional, but once given
+// each must exceed the last one accepted for the user and come from the
+// user's linked principal, so nobody else can resend a captured call under a
+// fresh nonce. The new value is only kept if the caller goes on to save the state.
+fn check_nonce(nonces: &mut RequestNonces, user: &User, nonce: Option<u64>) -> Result<(), AppError> {
+    let Some(nonce) = nonce else {
+        return Ok(());
+    };
+    let caller = authenticated_caller()?;
+    if user.principal != Some(caller) {
+        return Err(AppError::Unauthorized);
+    }
+    if nonces.get(&user.id).is_some_and(|last| nonce <= *last) {
+        return Err(AppError::ReplayedRequest);
+    }
+    nonces.insert(user.id.clone(), nonce);
+    Ok(())
+}
+
+// Redeem Rewards
+#[update]
+fn redeem_reward(user_id: String, reward_id: String, nonce: Option<u64>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    check_nonce(&mut state.request_nonces, &user, nonce)?;
+
+    if let Some(user) = state.users.get_mut(&user_id) {
+        if !user.email_verified {
+            return Err(AppError::EmailNotVerified);
+        }
+        if let Some(reward) = state.rewards.get(&reward_id) {
+            if user.tokens >= reward.cost_tokens {
+                user.tokens -= reward.cost_tokens;
+                record_token_txn(&mut state.token_history, &user_id, -(reward.cost_tokens as i64), &format!("Redeemed reward {}", reward_id));
+                record_activity(&mut state.activity, &user_id, ActivityKind::RewardRedeemed, &reward_id);
+                push_notification(&mut state.notifications, &user_id, state.settings.max_notifications, Notification {
+                    user_id: user_id.clone(),
+                    message: render_notification(NotificationType::RewardRedeemed, &user.preferred_language, &[&reward.description, &user.tokens.to_string()]),
+                    timestamp: env::time() as i64,
+                    notification_type: NotificationType::RewardRedeemed,
+                    read: false,
+                });
+                // Logic for granting the reward can go here
+                save_storage(state)?;
+                log_action(&format!("User {} redeemed reward {}", user_id, reward_id))?;
+                return Ok(format!("Reward {} redeemed successfully!", reward_id));
+            } else {
+                return Err(AppError::InvalidReward);
+            }
+        } else {
+            return Err(AppError::InvalidReward);
+        }
+    }
+
+    Err(AppError::UserNotFound)
+}
+
+// Record a Footprint
+#[update]
+fn record_footprint(user_id: String, waste_generated: u32, recyclable_waste: u32) -> Result<f32, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+
+    // Score is the share of generated waste that was recyclable, as a percentage
+    let footprint_score = if waste_generated == 0 {
+        0.0
+    } else {
+        recyclable_waste.min(waste_generated) as f32 * 100.0 / waste_generated as f32
+    };
+
+    state.footprints.entry(user_id.clone()).or_default().push((now(), UserFootprint {
+        waste_generated,
+        recyclable_waste,
+        footprint_score,
+    }));
+
+    // Award an achievement the first time the goal is reached before its deadline
+    if let Some(goal) = state.footprint_goals.get_mut(&user_id) {
+        if !goal.achieved && footprint_score >= goal.target_score && now() <= goal.deadline {
+            goal.achieved = true;
+            state.users.get_mut(&user_id).unwrap().achievements.push("Footprint Goal Reached".to_string());
+        }
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Footprint recorded for user {}", user_id))?;
+
+    Ok(footprint_score)
+}
+
+// Set a Footprint Goal
+#[update]
+fn set_footprint_goal(user_id: String, target_score: f32, deadline: i64) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+
+    state.footprint_goals.insert(user_id.clone(), FootprintGoal { target_score, deadline, achieved: false });
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} set a footprint goal of {}", user_id, target_score))?;
+
+    Ok("Footprint goal set successfully".to_string())
+}
+
+// Footprint Goal Progress
+#[query]
+fn get_goal_progress(user_id: String) -> Result<GoalProgress, AppError> {
+    let state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    let goal = state.footprint_goals.get(&user_id).ok_or(AppError::GoalNotFound)?;
+
+    let current_score = state.footprints.get(&user_id)
+        .and_then(|history| history.last())
+        .map_or(0.0, |(_, footprint)| footprint.footprint_score);
+
+    Ok(GoalProgress {
+        current_score,
+        target_score: goal.target_score,
+        deadline: goal.deadline,
+        deadline_passed: now() > goal.deadline,
+        achieved: goal.achieved,
+    })
+}
+
+// Footprint History Retrieval
+#[query]
+fn get_footprint_history(user_id: String) -> Vec<(i64, UserFootprint)> {
+    let state = restore_storage();
+    state.footprints.get(&user_id).cloned().unwrap_or_default()
+}
+
+// Latest Footprint Retrieval
+#[query]
+fn get_latest_footprint(user_id: String) -> Option<UserFootprint> {
+    let state = restore_storage();
+    state.footprints.get(&user_id)
+        .and_then(|history| history.last())
+        .map(|(_, footprint)| footprint.clone())
+}
+
+// Global Footprint Summary
+#[query]
+fn get_global_footprint_summary() -> FootprintSummary {
+    let state = restore_storage();
+
+    let mut summary = FootprintSummary { total_waste_generated: 0, total_recyclable_waste: 0, average_footprint_score: 0.0, users_counted: 0 };
+    let mut score_total = 0.0;
+    for (_, footprint) in state.footprints.values().filter_map(|history| history.last()) {
+        summary.total_waste_generated += footprint.waste_generated as u64;
+        summary.total_recyclable_waste += footprint.recyclable_waste as u64;
+        score_total += footprint.footprint_score;
+        summary.users_counted += 1;
+    }
+    if summary.users_counted > 0 {
+        summary.average_footprint_score = score_total / summary.users_counted as f32;
+    }
+
+    summary
+}
+
+// Footprint Leaderboard Retrieval
+#[query]
+fn get_footprint_leaderboard(offset: u32, limit: u32) -> Vec<(String, f32)> {
+    let state = restore_storage();
+    let mut leaderboard: Vec<(String, f32)> = state.footprints.iter()
+        .filter_map(|(id, history)| history.last().map(|(_, footprint)| (id.clone(), footprint.footprint_score)))
+        .collect();
+
+    leaderboard.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal)); // Sort by score descending
+    leaderboard.into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect()
+}
+
+// Mint Tokens
+#[update]
+fn mint_tokens(admin_id: String, user_id: String, amount: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    credit_tokens(user, amount)?;
+    state.supply.total_minted += amount as u64;
+    record_token_txn(&mut state.token_history, &user_id, amount as i64, "Minted by admin");
+
+    save_storage(state)?;
+
+    log_action(&format!("Admin {} minted {} tokens for user {}", admin_id, amount, user_id))?;
+
+    Ok(format!("Minted {} tokens", amount))
+}
+
+// Burn Tokens
+#[update]
+fn burn_tokens(admin_id: String, user_id: String, amount: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    let burned = amount.min(user.tokens); // Balances never go below zero
+    user.tokens -= burned;
+    state.supply.total_burned += burned as u64;
+    record_token_txn(&mut state.token_history, &user_id, -(burned as i64), "Burned by admin");
+
+    save_storage(state)?;
+
+    log_action(&format!("Admin {} burned {} tokens from user {}", admin_id, burned, user_id))?;
+
+    Ok(format!("Burned {} tokens", burned))
+}
+
+// Set a User's Token Balance (admin only), for corrections
+#[update]
+fn set_user_tokens(admin_id: String, user_id: String, tokens: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    let previous = user.tokens;
+    user.tokens = tokens;
+    // Adjustments count towards supply like admin mints and burns
+    if tokens > previous {
+        state.supply.total_minted += (tokens - previous) as u64;
+    } else {
+        state.supply.total_burned += (previous - tokens) as u64;
+    }
+    record_token_txn(&mut state.token_history, &user_id, tokens as i64 - previous as i64, "admin adjustment");
+
+    save_storage(state)?;
+
+    log_action(&format!("Admin {} set tokens of user {} from {} to {}", admin_id, user_id, previous, tokens))?;
+
+    Ok(format!("Token balance set to {}", tokens))
+}
+
+// Quiz Pass Details of a user, in the order passed
+#[query]
+fn get_my_quiz_results(user_id: String) -> Vec<QuizPassRecord> {
+    let state = restore_storage();
+    state.quiz_passes.get(&user_id).cloned().unwrap_or_default()
+}
+
+// Activity Feed Retrieval (newest first)
+#[query]
+fn get_activity_feed(user_id: String, offset: u32, limit: u32) -> Vec<ActivityEvent> {
+    let state = restore_storage();
+    state.activity.get(&user_id)
+        .map(|feed| feed.iter()
+            .rev()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+        .unwrap_or_default()
+}
+
+// Token History Retrieval (newest first)
+#[query]
+fn get_token_history(user_id: String, offset: u32, limit: u32) -> Vec<TokenTxn> {
+    let state = restore_storage();
+    state.token_history.get(&user_id)
+        .map(|history| history.iter()
+            .rev()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+        .unwrap_or_default()
+}
+
+// Map a token balance to its reputation tier
+fn reputation_tier(tokens: u32, thresholds: &TierThresholds) -> Tier {
+    if tokens >= thresholds.platinum {
+        Tier::Platinum
+    } else if tokens >= thresholds.gold {
+        Tier::Gold
+    } else if tokens >= thresholds.silver {
+        Tier::Silver
+    } else {
+        Tier::Bronze
+    }
+}
+
+// Reputation Retrieval
+#[query]
+fn get_reputation(user_id: String) -> Result<ReputationInfo, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    let thresholds = &state.tier_thresholds;
+
+    let tier = reputation_tier(user.tokens, thresholds);
+    let next = match tier {
+        Tier::Bronze => Some((Tier::Silver, thresholds.silver)),
+        Tier::Silver => Some((Tier::Gold, thresholds.gold)),
+        Tier::Gold => Some((Tier::Platinum, thresholds.platinum)),
+        Tier::Platinum => None,
+    };
+
+    Ok(ReputationInfo {
+        tier,
+        tokens: user.tokens,
+        next_tier: next.map(|(next_tier, _)| next_tier),
+        tokens_to_next_tier: next.map(|(_, threshold)| threshold - user.tokens).unwrap_or(0),
+    })
+}
+
+// Configure Reputation Tier Thresholds
+#[update]
+fn set_tier_thresholds(admin_id: String, thresholds: TierThresholds) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    if thresholds.silver == 0 || thresholds.silver >= thresholds.gold || thresholds.gold >= thresholds.platinum {
+        return Err(AppError::InvalidTierThresholds);
+    }
+    state.tier_thresholds = thresholds;
+
+    save_storage(state)?;
+
+    log_action(&format!("Tier thresholds updated by {}", admin_id))?;
+
+    Ok("Tier thresholds updated".to_string())
+}
+
+// ICRC-1 subaccount of an internal user
+fn user_subaccount(user_id: &str) -> Vec<u8> {
+    Sha256::digest(user_id.as_bytes()).to_vec()
+}
+
+// Internal user behind an ICRC-1 account, if any
+fn account_user(users: &Users, account: &Account) -> Option<User> {
+    if account.owner != env::id() {
+        return None;
+    }
+    let subaccount = account.subaccount.as_ref()?;
+    users.values().find(|user| user_subaccount(&user.id) == *subaccount)
+}
+
+// Link the calling principal to a user so it can make ICRC-1 transfers and,
+// for admins, admin calls
+#[update]
+fn link_principal(user_id: String, password: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    let caller = authenticated_caller()?;
+    let user = state.users.get_mut(&user_id).ok_or(AppError::UserNotFound)?;
+    if user.hashed_password != hash_password(&password, &user.salt) {
+        return Err(AppError::InvalidCredentials);
+    }
+    user.principal = Some(caller);
+
+    save_storage(state)?;
+
+    log_action(&format!("User {} linked principal {}", user_id, caller))?;
+
+    Ok("Principal linked successfully".to_string())
+}
+
+// ICRC-1 account of a user
+#[query]
+fn get_account(user_id: String) -> Result<Account, AppError> {
+    let state = restore_storage();
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    Ok(Account { owner: env::id(), subaccount: Some(user_subaccount(&user_id)) })
+}
+
+#[query]
+fn icrc1_name() -> String {
+    "RepuCycle Token".to_string()
+}
+
+#[query]
+fn icrc1_symbol() -> String {
+    "RCT".to_string()
+}
+
+#[query]
+fn icrc1_decimals() -> u8 {
+    0 // Tokens are whole units
+}
+
+#[query]
+fn icrc1_balance_of(account: Account) -> u128 {
+    let state = restore_storage();
+    account_user(&state.users, &account).map(|user| user.tokens as u128).unwrap_or(0)
+}
+
+// Count a transfer against the sender's limits. The daily window restarts a
+// day after the first transfer made in it.
+fn apply_transfer_limits(settings: &Settings, windows: &mut TransferWindows, user_id: &str, amount: u32) -> Result<(), AppError> {
+    if amount > settings.max_transfer_amount {
+        return Err(AppError::TransferLimitExceeded);
     }
+    let current_time = now();
+    let window = windows.entry(user_id.to_string()).or_insert((current_time, 0));
+    if current_time - window.0 >= SECONDS_PER_DAY {
+        *window = (current_time, 0);
+    }
+    if window.1.saturating_add(amount) > settings.daily_transfer_cap {
+        return Err(AppError::TransferLimitExceeded);
+    }
+    window.1 += amount;
+    Ok(())
+}
 
-    courses.insert(title.clone(), Course { 
-        title: title.clone(), 
-        levels, 
-        educational_resources 
-    });
+// Configure Transfer Limits (admin only)
+#[update]
+fn set_transfer_limits(admin_id: String, max_transfer_amount: u32, daily_transfer_cap: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
 
-    save_storage(
-        users,
-        HashMap::<String, UserFootprint>::new(), 
-        HashMap::<String, Quiz>::new(), 
-        HashMap::<String, Challenge>::new(), 
-        HashMap::<String, Token>::new(),
-        Vec::<String>::new(),
-        courses,
-        HashMap::<String, Vec<Notification>>::new(), 
-        Vec::<Feedback>::new(),
-        HashMap::<String, Reward>::new(),
-    )?;
-    
-    log_action(&format!("Course {} added", title))?;
-    
-    Ok("Course added successfully".to_string())
+    require_admin(&state.users, &admin_id)?;
+    state.settings.max_transfer_amount = max_transfer_amount;
+    state.settings.daily_transfer_cap = daily_transfer_cap;
+
+    save_storage(state)?;
+
+    log_action(&format!("Transfer limits set to {} per transfer and {} per day by {}", max_transfer_amount, daily_transfer_cap, admin_id))?;
+
+    Ok("Transfer limits updated".to_string())
 }
 
-// Submit Feedback
+// ICRC-1 transfer between internal accounts; transfers are free
 #[update]
-fn submit_feedback(user_id: String, feedback: String) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, _, mut feedbacks, _) = restore_storage();
+fn icrc1_transfer(args: TransferArg) -> Result<u128, TransferError> {
+    let mut state = restore_storage();
 
-    if !users.contains_key(&user_id) {
-        return Err(AppError::UserNotFound);
+    if args.fee.is_some_and(|fee| fee != 0) {
+        return Err(TransferError::BadFee { expected_fee: 0 });
+    }
+    if args.memo.as_ref().is_some_and(|memo| memo.len() > ICRC1_MAX_MEMO_BYTES) {
+        return Err(TransferError::GenericError { error_code: 0, message: "Memo too long".to_string() });
+    }
+    let ledger_time = env::time();
+    if let Some(created_at_time) = args.created_at_time {
+        if created_at_time.checked_add(ICRC1_TX_WINDOW_NANOS).is_some_and(|window_end| window_end < ledger_time) {
+            return Err(TransferError::TooOld);
+        }
+        if created_at_time > ledger_time.saturating_add(ICRC1_PERMITTED_DRIFT_NANOS) {
+            return Err(TransferError::CreatedInFuture { ledger_time });
+        }
     }
 
-    feedbacks.push(Feedback { user_id: user_id.clone(), feedback });
-    
-    save_storage(
-        users,
-        HashMap::<String, UserFootprint>::new(), 
-        HashMap::<String, Quiz>::new(), 
-        HashMap::<String, Challenge>::new(), 
-        HashMap::<String, Token>::new(),
-        Vec::<String>::new(),
-        HashMap::<String, Course>::new(), 
-        HashMap::<String, Vec<Notification>>::new(), 
-        feedbacks,
-        HashMap::<String, Reward>::new(),
-    )?;
-    
-    log_action(&format!("Feedback submitted by user {}", user_id))?;
-    
-    Ok("Feedback submitted successfully".to_string())
+    let caller = env::caller();
+    let from = state.users.values()
+        .find(|user| user.principal == Some(caller))
+        .filter(|user| args.from_subaccount.as_ref().is_none_or(|subaccount| *subaccount == user_subaccount(&user.id)))
+        .ok_or(TransferError::GenericError { error_code: 1, message: "Caller is not linked to this account".to_string() })?;
+    let to = account_user(&state.users, &args.to)
+        .ok_or(TransferError::GenericError { error_code: 2, message: "Unknown destination account".to_string() })?;
+
+    // Only transfers with a created_at_time are deduplicated, as ICRC-1
+    // specifies: an identical transfer inside the window is a duplicate
+    state.recent_transfer_hashes.retain(|_, transfer| {
+        transfer.created_at_time.saturating_add(ICRC1_TX_WINDOW_NANOS + ICRC1_PERMITTED_DRIFT_NANOS) >= ledger_time
+    });
+    let tx_hash = args.created_at_time.map(|_| transfer_hash(&from.id, &to.id, &args));
+    if let Some(duplicate) = tx_hash.as_ref().and_then(|tx_hash| state.recent_transfer_hashes.get(tx_hash)) {
+        return Err(TransferError::Duplicate { duplicate_of: duplicate.block_index });
+    }
+
+    if args.amount > from.tokens as u128 {
+        return Err(TransferError::InsufficientFunds { balance: from.tokens as u128 });
+    }
+    let amount = args.amount as u32;
+    apply_transfer_limits(&state.settings, &mut state.transfer_windows, &from.id, amount)
+        .map_err(|e| TransferError::GenericError { error_code: 4, message: e.to_string() })?;
+
+    state.users.get_mut(&from.id).unwrap().tokens -= amount;
+    credit_tokens(state.users.get_mut(&to.id).unwrap(), amount)
+        .map_err(|e| TransferError::GenericError { error_code: 6, message: e.to_string() })?;
+    record_token_txn(&mut state.token_history, &from.id, -(amount as i64), &format!("Transferred to {}", to.id));
+    record_token_txn(&mut state.token_history, &to.id, amount as i64, &format!("Received from {}", from.id));
+
+    let block_index = state.transfer_count;
+    state.transfer_count += 1;
+    if let (Some(tx_hash), Some(created_at_time)) = (tx_hash, args.created_at_time) {
+        state.recent_transfer_hashes.insert(tx_hash, RecentTransfer { created_at_time, block_index });
+    }
+
+    save_storage(state).map_err(|e| TransferError::GenericError { error_code: 3, message: e.to_string() })?;
+
+    log_action(&format!("Transfer {} of {} tokens from {} to {}", block_index, amount, from.id, to.id))
+        .map_err(|e| TransferError::GenericError { error_code: 3, message: e.to_string() })?;
+
+    Ok(block_index)
 }
 
-// Add a Challenge
+// Identifies a transfer for deduplication; every field the caller sets is
+// part of it, so only an exact retry hashes the same
+fn transfer_hash(from: &str, to: &str, args: &TransferArg) -> String {
+    let fields = (from, to, args.amount, &args.fee, &args.memo, args.created_at_time);
+    let bytes = serde_cbor::to_vec(&fields).expect("failed to encode transfer");
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+// Configure the External Ledger (admin only)
 #[update]
-fn add_challenge(description: String, reward_tokens: u32, required_courses: Vec<String>, required_quizzes: Vec<String>) -> Result<String, AppError> {
-    let (mut users, _, _, mut challenges, _, _, _, _, _, _) = restore_storage();
+fn set_ledger_canister(admin_id: String, ledger_canister_id: Option<Principal>) -> Result<String, AppError> {
+    let mut state = restore_storage();
 
-    let id = Uuid::new_v4().to_string();
-    challenges.insert(id.clone(), Challenge { 
-        description, 
-        reward_tokens, 
-        required_courses, 
-        required_quizzes,
-        participants: Vec::new(), 
-    });
+    require_admin(&state.users, &admin_id)?;
+    state.settings.ledger_canister_id = ledger_canister_id;
 
-    save_storage(
-        users,
-        HashMap::<String, UserFootprint>::new(), 
-        HashMap::<String, Quiz>::new(), 
-        challenges,
-        HashMap::<String, Token>::new(),
-        Vec::<String>::new(),
-        HashMap::<String, Course>::new(), 
-        HashMap::<String, Vec<Notification>>::new(), 
-        Vec::<Feedback>::new(),
-        HashMap::<String, Reward>::new(),
-    )?;
-    
-    log_action(&format!("Challenge {} added", id))?;
-    
-    Ok("Challenge added successfully".to_string())
+    save_storage(state)?;
+
+    log_action(&format!("Ledger canister set to {:?} by {}", ledger_canister_id, admin_id))?;
+
+    Ok("Ledger canister updated".to_string())
 }
 
-// Submit a Social Notification
+// Cash Out tokens to the user's linked principal on the external ledger; the
+// call must come from that principal
 #[update]
-fn send_notification(from_user: String, to_user: String, message: String) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, mut notifications, _, _) = restore_storage();
+async fn cash_out(user_id: String, amount: u32, nonce: Option<u64>) -> Result<u128, AppError> {
+    let mut state = restore_storage();
 
-    if !users.contains_key(&from_user) || !users.contains_key(&to_user) {
-        return Err(AppError::UserNotFound);
+    let caller = authenticated_caller()?;
+    let ledger = state.settings.ledger_canister_id.ok_or(AppError::LedgerNotConfigured)?;
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    let owner = user.principal.ok_or(AppError::PrincipalNotLinked)?;
+    if owner != caller {
+        return Err(AppError::Unauthorized);
     }
+    if user.tokens < amount {
+        return Err(AppError::InsufficientTokens);
+    }
+    check_nonce(&mut state.request_nonces, &user, nonce)?;
 
-    let notification = Notification {
-        user_id: to_user.clone(),
-        message: format!("{}: {}", from_user, message),
-        timestamp: ic_cdk::api::time() as i64,
-        notification_type: NotificationType::AchievementShared,
+    // Hold the tokens while the call is in flight so they cannot be spent twice
+    state.users.get_mut(&user_id).unwrap().tokens -= amount;
+    let cash_out = PendingCashOut { id: generate_id(), user_id: user_id.clone(), amount, to: owner, created_at_time: env::time() };
+    state.pending_cash_outs.insert(cash_out.id.clone(), cash_out.clone());
+    save_storage(state)?;
+
+    let arg = TransferArg {
+        from_subaccount: None,
+        to: Account { owner, subaccount: None },
+        amount: amount as u128,
+        fee: None,
+        memo: Some(Sha256::digest(cash_out.id.as_bytes()).to_vec()), // Ledgers cap memos at 32 bytes
+        created_at_time: Some(cash_out.created_at_time),
     };
+    let outcome = transfer_outcome(ic_cdk::call(ledger, "icrc1_transfer", (arg,)).await);
 
-    notifications.entry(to_user.clone()).or_insert(Vec::new()).push(notification);
-    users.get_mut(&from_user).unwrap().notifications.push(format!("You sent a message to {}", to_user));
+    let mut state = restore_storage();
+    let result = settle_cash_out(&mut state, &cash_out.id, &outcome);
+    save_storage(state)?;
 
-    save_storage(users, HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new(), Vec::new(), HashMap::new(), notifications, Vec::new(), HashMap::new())?;
+    log_action(&format!("User {} cash-out {} of {} tokens: {:?}", user_id, cash_out.id, amount, outcome))?;
 
-    log_action(&format!("Notification sent from {} to {}", from_user, to_user))?;
-    
-    Ok("Notification sent successfully".to_string())
+    result
 }
 
-// Redeem Rewards
-#[update]
-fn redeem_reward(user_id: String, reward_id: String) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, _, _, mut rewards) = restore_storage();
+fn transfer_outcome(result: CallResult<(Result<u128, TransferError>,)>) -> TransferOutcome {
+    match result {
+        Ok((Ok(block_index),)) => TransferOutcome::Completed(block_index),
+        // An identical earlier request already went through
+        Ok((Err(TransferError::Duplicate { duplicate_of }),)) => TransferOutcome::Completed(duplicate_of),
+        Ok((Err(e),)) => TransferOutcome::Rejected(format!("{:?}", e)),
+        // The message never reached the ledger
+        Err((code @ (RejectionCode::SysTransient | RejectionCode::DestinationInvalid), message)) => {
+            TransferOutcome::Rejected(format!("{:?}: {}", code, message))
+        }
+        Err((code, message)) => TransferOutcome::Unknown(format!("{:?}: {}", code, message)),
+    }
+}
 
-    if let Some(user) = users.get_mut(&user_id) {
-        if let Some(reward) = rewards.get(&reward_id) {
-            if user.tokens >= reward.cost_tokens {
-                user.tokens -= reward.cost_tokens;
-                // Logic for granting the reward can go here
-                log_action(&format!("User {} redeemed reward {}", user_id, reward_id))?;
-                return Ok(format!("Reward {} redeemed successfully!", reward_id));
-            } else {
-                return Err(AppError::InvalidReward);
+// Keep the held tokens of a completed cash-out and release those of a
+// rejected one. An unknown outcome stays pending, tokens held, until an
+// admin checks the ledger and resolves it.
+fn settle_cash_out(state: &mut State, cash_out_id: &str, outcome: &TransferOutcome) -> Result<u128, AppError> {
+    let cash_out = state.pending_cash_outs.get(cash_out_id).cloned().ok_or(AppError::CashOutNotFound)?;
+    match outcome {
+        TransferOutcome::Completed(block_index) => {
+            state.pending_cash_outs.remove(cash_out_id);
+            record_token_txn(&mut state.token_history, &cash_out.user_id, -(cash_out.amount as i64), &format!("Cashed out to ledger (block {})", block_index));
+            Ok(*block_index)
+        }
+        TransferOutcome::Rejected(reason) => {
+            // A refund that cannot be credited leaves the cash-out pending
+            if let Some(user) = state.users.get_mut(&cash_out.user_id) {
+                credit_tokens(user, cash_out.amount)?;
             }
-        } else {
-            return Err(AppError::InvalidReward);
+            state.pending_cash_outs.remove(cash_out_id);
+            Err(AppError::LedgerError(reason.clone()))
         }
+        TransferOutcome::Unknown(reason) => Err(AppError::CashOutPending(reason.clone())),
     }
-
-    Err(AppError::UserNotFound)
 }
 
-// Leaderboard Retrieval
+// Cash-outs Awaiting Reconciliation (admin only)
 #[query]
-fn get_leaderboard() -> Vec<(String, u32)> {
-    let (users, _, _, _, _, _, _, _, _, _) = restore_storage();
+fn get_pending_cash_outs(admin_id: String) -> Result<Vec<PendingCashOut>, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let mut pending: Vec<PendingCashOut> = state.pending_cash_outs.values().cloned().collect();
+    pending.sort_by_key(|cash_out| cash_out.created_at_time);
+    Ok(pending)
+}
+
+// Resolve a Pending Cash-out from the ledger's records (admin only): the
+// block index if the transfer is on the ledger, or None to refund the user
+#[update]
+fn resolve_cash_out(admin_id: String, cash_out_id: String, block_index: Option<u128>) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let outcome = match block_index {
+        Some(block_index) => TransferOutcome::Completed(block_index),
+        None => TransferOutcome::Rejected(format!("Not found on the ledger by {}", admin_id)),
+    };
+    match settle_cash_out(&mut state, &cash_out_id, &outcome) {
+        Ok(_) | Err(AppError::LedgerError(_)) => {} // A refund settles as the transfer's error
+        Err(e) => return Err(e),
+    }
+
+    save_storage(state)?;
+
+    log_action(&format!("Cash-out {} resolved by {}: {:?}", cash_out_id, admin_id, outcome))?;
+
+    Ok("Cash-out resolved".to_string())
+}
+
+// Rank users by tokens, optionally restricted to a single role
+fn ranked_users(users: &Users, role: Option<&Role>) -> Vec<(String, u32)> {
     let mut leaderboard: Vec<(String, u32)> = users.iter()
-        .map(|(id, user)| (id.clone(), user.tokens))
+        .filter(|(_, user)| role.is_none_or(|role| &user.role == role))
+        .map(|(id, user)| (id, user.tokens))
         .collect();
     
-    leaderboard.sort_by(|a, b| b.1.cmp(&a.1)); // Sort by tokens descending
+    leaderboard.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens)); // Sort by tokens descending
     leaderboard
 }
 
-// Utility to validate data
-fn validate_data() -> Result<(), AppError> {
-    let (_, _footprints, _quizzes, _challenges, _, _, courses, _, _, _) = restore_storage();
-    
-    // Perform data validation here...
-    
-    Ok(())
+// Leaderboard Retrieval
+#[query]
+fn get_leaderboard() -> Vec<(String, u32)> {
+    let state = restore_storage();
+    ranked_users(&state.users, Some(&Role::User)) // Staff accounts are kept off the public leaderboard
+}
+
+// Rank Change since the last snapshot; positive means the user moved up.
+// Users absent from the snapshot or the public leaderboard report 0.
+#[query]
+fn get_rank_change(user_id: String) -> Result<i32, AppError> {
+    let state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    let Some(previous) = state.rank_snapshot.get(&user_id) else {
+        return Ok(0);
+    };
+    let current = ranked_users(&state.users, Some(&Role::User))
+        .iter()
+        .position(|(id, _)| id == &user_id)
+        .map(|index| index as i64 + 1);
+
+    Ok(current.map_or(0, |current| (*previous as i64 - current) as i32))
+}
+
+// Leaderboard Neighbourhood: the user plus `radius` entries above and below
+#[query]
+fn get_leaderboard_around_me(user_id: String, radius: u32) -> Result<Vec<LeaderboardEntry>, AppError> {
+    let state = restore_storage();
+
+    let user = state.users.get(&user_id).ok_or(AppError::UserNotFound)?;
+    // Staff are ranked against everyone since they are not on the public leaderboard
+    let role = if user.role == Role::User { Some(&Role::User) } else { None };
+    let leaderboard = ranked_users(&state.users, role);
+    let position = leaderboard.iter().position(|(id, _)| id == &user_id).ok_or(AppError::UserNotFound)?;
+
+    let start = position.saturating_sub(radius as usize);
+    let end = position.saturating_add(radius as usize).saturating_add(1).min(leaderboard.len());
+    Ok(leaderboard[start..end].iter()
+        .enumerate()
+        .map(|(index, (id, tokens))| LeaderboardEntry {
+            rank: (start + index + 1) as u32,
+            user_id: id.clone(),
+            tokens: *tokens,
+        })
+        .collect())
+}
+
+// Challenge Leaderboard Retrieval
+#[query]
+fn get_challenge_leaderboard(challenge_id: String) -> Result<Vec<(String, u32)>, AppError> {
+    let state = restore_storage();
+
+    let challenge = state.challenges.get(&challenge_id).ok_or(AppError::ChallengeNotFound)?;
+    let mut leaderboard: Vec<(String, u32)> = challenge.participants.iter()
+        .filter_map(|id| state.users.get(id).map(|user| (id.clone(), user.tokens)))
+        .collect();
+
+    leaderboard.sort_by_key(|(_, tokens)| std::cmp::Reverse(*tokens)); // Sort by tokens descending
+    Ok(leaderboard)
+}
+
+// Leaderboard Retrieval by Role. Anyone may page through users; a view that
+// includes admins (role Admin or None for everyone) is for admins only.
+#[query]
+fn get_leaderboard_by_role(admin_id: Option<String>, role: Option<Role>, offset: u32, limit: u32) -> Result<Vec<(String, u32)>, AppError> {
+    let state = restore_storage();
+
+    if role != Some(Role::User) {
+        require_admin(&state.users, admin_id.as_deref().unwrap_or_default())?;
+    }
+
+    Ok(ranked_users(&state.users, role.as_ref())
+        .into_iter()
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect())
+}
+
+// Data Export (admin only). The JSON document is returned in chunks that the
+// caller concatenates, starting from chunk 0 until total_chunks is reached.
+#[query]
+fn export_data(admin_id: String, chunk: u32) -> Result<ExportChunk, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let users: Vec<User> = state.users.values()
+        .map(|mut user| {
+            user.hashed_password = "[redacted]".to_string();
+            user.salt = "[redacted]".to_string();
+            user.sessions.clear();
+            user.password_reset = None;
+            user.email_verification_token = None;
+            user
+        })
+        .collect();
+    let courses: Vec<Course> = state.courses.values().collect();
+    let rewards: Vec<&Reward> = state.rewards.values().collect();
+
+    let document = serde_json::to_string(&json!({
+        "users": users,
+        "courses": courses,
+        "challenges": state.challenges,
+        "rewards": rewards,
+    })).map_err(|e| AppError::ExportError(e.to_string()))?;
+
+    // Split on character boundaries so every chunk is valid UTF-8
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < document.len() {
+        let mut end = (start + EXPORT_CHUNK_BYTES).min(document.len());
+        while !document.is_char_boundary(end) {
+            end -= 1;
+        }
+        chunks.push(&document[start..end]);
+        start = end;
+    }
+
+    Ok(ExportChunk {
+        data: chunks.get(chunk as usize).map(|data| data.to_string()).unwrap_or_default(),
+        chunk,
+        total_chunks: chunks.len() as u32,
+    })
+}
+
+// Nearby Recycling Centers from OpenStreetMap, cached per ~1km area. Each
+// outcall costs cycles, so only logged-in users may trigger one, at most
+// once per RECYCLING_FETCH_COOLDOWN_SECS; cached areas are always served.
+#[update]
+async fn fetch_recycling_centers(user_id: String, token: String, lat: f64, lon: f64) -> Result<Vec<RecyclingCenter>, AppError> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(AppError::InvalidCoordinates);
+    }
+    let lat = (lat * 100.0).round() / 100.0;
+    let lon = (lon * 100.0).round() / 100.0;
+    let key = format!("{:.2},{:.2}", lat, lon);
+
+    {
+        let mut state = restore_storage();
+        let user = is_logged_in(&state.users, &user_id, &token)?;
+        if let Some((fetched_at, centers)) = state.recycling_centers.get(&key) {
+            if now() - fetched_at < state.settings.recycling_cache_ttl_secs as i64 {
+                return Ok(centers);
+            }
+        }
+
+        let wait = user.last_recycling_fetch + RECYCLING_FETCH_COOLDOWN_SECS - now();
+        if wait > 0 {
+            return Err(AppError::TooManyRequests(wait as u64));
+        }
+        // Recorded before the outcall so calls made while it runs are limited too
+        state.users.get_mut(&user_id).unwrap().last_recycling_fetch = now();
+        save_storage(state)?;
+    }
+
+    let query = format!("[out:json];node[amenity=recycling](around:{},{},{});out;", RECYCLING_SEARCH_RADIUS_METERS, lat, lon);
+    let request = CanisterHttpRequestArgument {
+        url: format!("{}?data={}", OVERPASS_API_URL, percent_encode(&query)),
+        method: HttpMethod::GET,
+        body: None,
+        max_response_bytes: Some(RECYCLING_MAX_RESPONSE_BYTES),
+        transform: Some(TransformContext::from_name("transform_recycling_centers".to_string(), vec![])),
+        headers: vec![HttpHeader { name: "User-Agent".to_string(), value: "repucycle".to_string() }],
+    };
+
+    let (response,) = http_request(request, HTTP_OUTCALL_CYCLES).await
+        .map_err(|(code, message)| AppError::HttpOutcallError(format!("{:?}: {}", code, message)))?;
+    if response.status != 200u32 {
+        return Err(AppError::HttpOutcallError(format!("status {}", response.status)));
+    }
+    let centers: Vec<RecyclingCenter> = serde_json::from_slice(&response.body)
+        .map_err(|e| AppError::HttpOutcallError(e.to_string()))?;
+
+    // Other calls may have run during the outcall, so reload before writing
+    let mut state = restore_storage();
+    let ttl_secs = state.settings.recycling_cache_ttl_secs;
+    cache_recycling_centers(&mut state.recycling_centers, &key, centers.clone(), ttl_secs);
+
+    save_storage(state)?;
+
+    log_action(&format!("Recycling centers fetched for {}", key))?;
+
+    Ok(centers)
+}
+
+// Cache an area's centers, dropping expired areas and then, past
+// RECYCLING_CACHE_MAX_ENTRIES, the longest cached ones
+fn cache_recycling_centers(cache: &mut RecyclingCenterCache, key: &str, centers: Vec<RecyclingCenter>, ttl_secs: u64) {
+    let (expired, mut live): (Vec<_>, Vec<_>) = cache.iter()
+        .map(|(area, (fetched_at, _))| (area, fetched_at))
+        .filter(|(area, _)| area != key)
+        .partition(|(_, fetched_at)| now() - fetched_at >= ttl_secs as i64);
+
+    live.sort_by_key(|(_, fetched_at)| *fetched_at);
+    let excess = (live.len() + 1).saturating_sub(RECYCLING_CACHE_MAX_ENTRIES);
+    for (area, _) in expired.iter().chain(live.iter().take(excess)) {
+        cache.remove(area);
+    }
+    cache.insert(key.to_string(), (now(), centers));
+}
+
+// Reduce an Overpass response to a sorted center list so every replica sees
+// identical bytes; headers are dropped for the same reason
+#[query]
+fn transform_recycling_centers(raw: TransformArgs) -> HttpResponse {
+    let body = if raw.response.status == 200u32 {
+        serde_json::to_vec(&parse_overpass_centers(&raw.response.body)).unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    HttpResponse { status: raw.response.status, headers: Vec::new(), body }
+}
+
+fn parse_overpass_centers(body: &[u8]) -> Vec<RecyclingCenter> {
+    let Ok(document) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return Vec::new();
+    };
+    let mut centers: Vec<RecyclingCenter> = document["elements"].as_array()
+        .map(|elements| elements.iter()
+            .filter_map(|element| {
+                let tags = &element["tags"];
+                Some(RecyclingCenter {
+                    id: element["id"].as_u64()?,
+                    name: tags["name"].as_str().unwrap_or("Recycling point").to_string(),
+                    lat: element["lat"].as_f64()?,
+                    lon: element["lon"].as_f64()?,
+                    materials: tags.as_object()
+                        .map(|tags| tags.iter()
+                            .filter(|(tag, value)| tag.starts_with("recycling:") && value.as_str() == Some("yes"))
+                            .map(|(tag, _)| tag["recycling:".len()..].to_string())
+                            .collect())
+                        .unwrap_or_default(),
+                })
+            })
+            .collect())
+        .unwrap_or_default();
+    centers.sort_by_key(|center| center.id);
+    centers
+}
+
+// Percent-encode a URL query value
+fn percent_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+// Configure the Recycling Center Cache Lifetime (admin only)
+#[update]
+fn set_recycling_cache_ttl(admin_id: String, ttl_secs: u64) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.recycling_cache_ttl_secs = ttl_secs;
+
+    save_storage(state)?;
+
+    log_action(&format!("Recycling cache TTL set to {}s by {}", ttl_secs, admin_id))?;
+
+    Ok("Recycling cache TTL updated".to_string())
+}
+
+// Purge notifications, log entries and token history older than a cutoff
+// (admin only). Users, courses and challenges are never touched.
+#[update]
+fn purge_old_data(admin_id: String, older_than_secs: u64) -> Result<PurgeReport, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let cutoff = now().saturating_sub(i64::try_from(older_than_secs).unwrap_or(i64::MAX));
+    let cutoff_nanos = cutoff.saturating_mul(1_000_000_000); // Notifications and log entries use nanoseconds
+    let mut report = PurgeReport { notifications: 0, log_entries: 0, token_history: 0 };
+
+    let mut purged = Vec::new();
+    for (user_id, notifications) in state.notifications.iter() {
+        let kept: Vec<Notification> = notifications.iter()
+            .filter(|notification| notification.timestamp >= cutoff_nanos)
+            .cloned()
+            .collect();
+        if kept.len() < notifications.len() {
+            report.notifications += (notifications.len() - kept.len()) as u64;
+            purged.push((user_id, kept));
+        }
+    }
+    for (user_id, kept) in purged {
+        state.notifications.insert(user_id, kept);
+    }
+
+    let log_len = state.log.len();
+    state.log.retain(|entry| {
+        serde_json::from_str::<serde_json::Value>(entry).ok()
+            .and_then(|entry| entry["timestamp"].as_i64())
+            .is_none_or(|timestamp| timestamp >= cutoff_nanos)
+    });
+    report.log_entries = (log_len - state.log.len()) as u64;
+
+    for history in state.token_history.values_mut() {
+        let history_len = history.len();
+        history.retain(|txn| txn.timestamp >= cutoff);
+        report.token_history += (history_len - history.len()) as u64;
+    }
+    state.token_history.retain(|_, history| !history.is_empty());
+
+    save_storage(state)?;
+
+    log_action(&format!(
+        "Purged {} notifications, {} log entries and {} token history records older than {}s by {}",
+        report.notifications, report.log_entries, report.token_history, older_than_secs, admin_id
+    ))?;
+
+    Ok(report)
+}
+
+// System Statistics (admin only)
+#[query]
+fn get_stats(admin_id: String) -> Result<SystemStats, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    let current_time = now();
+    let mut stats = SystemStats {
+        total_users: 0,
+        total_courses: state.courses.iter().count() as u64,
+        total_challenges: state.challenges.len() as u64,
+        tokens_in_circulation: 0,
+        total_feedback: state.feedbacks.len() as u64,
+        active_sessions: 0,
+    };
+    for user in state.users.values() {
+        stats.total_users += 1;
+        stats.tokens_in_circulation += user.tokens as u64;
+        stats.active_sessions += user.sessions.iter().filter(|session| session.expires_at >= current_time).count() as u64;
+    }
+
+    Ok(stats)
+}
+
+fn encoded_len<T: Serialize>(value: &T) -> Result<u64, AppError> {
+    serde_cbor::to_vec(value)
+        .map(|bytes| bytes.len() as u64)
+        .map_err(|e| AppError::StorageError(e.to_string()))
+}
+
+// Cycles Balance of the canister
+#[query]
+fn get_cycles_balance() -> u64 {
+    env::canister_balance()
+}
+
+// Configure the Low Cycles Alert Threshold (admin only)
+#[update]
+fn set_low_cycles_threshold(admin_id: String, threshold: u64) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    state.settings.low_cycles_threshold = threshold;
+
+    save_storage(state)?;
+
+    log_action(&format!("Low cycles threshold set to {} by {}", threshold, admin_id))?;
+
+    Ok("Low cycles threshold updated".to_string())
+}
+
+// Storage Usage by collection (admin only)
+#[query]
+fn get_storage_usage(admin_id: String) -> Result<StorageUsage, AppError> {
+    let state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+
+    Ok(StorageUsage {
+        users_bytes: encoded_len(&state.users.iter().collect::<Vec<_>>())?,
+        courses_bytes: encoded_len(&state.courses.iter().collect::<Vec<_>>())?,
+        notifications_bytes: encoded_len(&state.notifications.iter().collect::<Vec<_>>())?,
+        log_bytes: encoded_len(&state.log)?,
+        state_bytes: STATE.with(|cell| cell.borrow().get().len() as u64),
+        stable_memory_pages: stable_memory().size(),
+    })
+}
+
+// Append a notification, evicting the oldest once the backlog exceeds the cap
+fn push_notification(notifications: &mut Notifications, user_id: &str, cap: u32, notification: Notification) {
+    let list = notifications.get_mut_or_default(user_id);
+    list.push(notification);
+    if list.len() > cap as usize {
+        let excess = list.len() - cap as usize;
+        list.drain(..excess);
+    }
+}
+
+// Configure the Notification Backlog Cap (admin only). Existing backlogs are
+// trimmed as new notifications arrive.
+#[update]
+fn set_max_notifications(admin_id: String, max_notifications: u32) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    require_admin(&state.users, &admin_id)?;
+    if !(1..=MAX_NOTIFICATIONS_CAP).contains(&max_notifications) {
+        return Err(AppError::InvalidSetting(format!("max_notifications must be between 1 and {}", MAX_NOTIFICATIONS_CAP)));
+    }
+    state.settings.max_notifications = max_notifications;
+
+    save_storage(state)?;
+
+    log_action(&format!("Notification backlog cap set to {} by {}", max_notifications, admin_id))?;
+
+    Ok("Notification backlog cap updated".to_string())
 }
 
 // Notification Management
 #[update]
 fn add_notification(user_id: String, message: String, notification_type: NotificationType) -> Result<String, AppError> {
-    let (mut users, _, _, _, _, _, _, mut notifications, _, _) = restore_storage();
+    let mut state = restore_storage();
 
-    if !users.contains_key(&user_id) {
+    validate_len("message", &message, MAX_MESSAGE_LEN)?;
+    if !state.users.contains_key(&user_id) {
         return Err(AppError::UserNotFound);
     }
 
+    // Free text from the caller has no translations, so it is stored as written
     let notification = Notification {
         user_id: user_id.clone(),
         message,
-        timestamp: ic_cdk::api::time() as i64,
+        timestamp: env::time() as i64,
         notification_type,
+        read: false,
     };
 
-    notifications.entry(user_id.clone()).or_insert(Vec::new()).push(notification);
+    push_notification(&mut state.notifications, &user_id, state.settings.max_notifications, notification);
 
-    save_storage(users, HashMap::new(), HashMap::new(), HashMap::new(), HashMap::new(), Vec::new(), HashMap::new(), notifications, Vec::new(), HashMap::new())?;
+    save_storage(state)?;
 
     log_action(&format!("Notification added for user {}", user_id))?;
 
     Ok("Notification added successfully".to_string())
-}
\ No newline at end of file
+}
+
+// Notification Retrieval (newest first)
+#[query]
+fn get_notifications(user_id: String, offset: u32, limit: u32, only_unread: bool) -> Vec<Notification> {
+    let state = restore_storage();
+    state.notifications.get(&user_id)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .filter(|notification| !only_unread || !notification.read)
+        .skip(offset as usize)
+        .take(limit as usize)
+        .collect()
+}
+
+// Notifications newer than a timestamp (oldest first), for polling clients.
+// since_ns is in nanoseconds, like Notification::timestamp, so a client can
+// pass back the timestamp of the newest notification it has seen.
+#[query]
+fn get_notifications_since(user_id: String, since_ns: i64) -> Vec<Notification> {
+    let state = restore_storage();
+    let mut notifications: Vec<Notification> = state.notifications.get(&user_id)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|notification| notification.timestamp > since_ns)
+        .collect();
+    notifications.sort_by_key(|notification| notification.timestamp);
+    notifications
+}
+
+// Mark all of a user's notifications as read
+#[update]
+fn mark_notifications_read(user_id: String) -> Result<String, AppError> {
+    let mut state = restore_storage();
+
+    if !state.users.contains_key(&user_id) {
+        return Err(AppError::UserNotFound);
+    }
+    if let Some(notifications) = state.notifications.get_mut(&user_id) {
+        notifications.iter_mut().for_each(|notification| notification.read = true);
+    }
+
+    save_storage(state)?;
+
+    Ok("Notifications marked as read".to_string())
+}
+
+// Generate the Candid interface from the annotated endpoints
+ic_cdk::export_candid!();
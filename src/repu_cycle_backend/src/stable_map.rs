@@ -0,0 +1,241 @@
+use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{DefaultMemoryImpl, StableBTreeMap, Storable};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
+use std::iter::Peekable;
+use std::ops::Bound as RangeBound;
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+thread_local! {
+    // On the IC this is the canister's stable memory; natively it is a vector
+    // shared by every handle, so tests see one consistent memory
+    static STABLE_MEMORY: DefaultMemoryImpl = DefaultMemoryImpl::default();
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(stable_memory()));
+}
+
+// The raw stable memory, underneath the memory manager's regions
+pub fn stable_memory() -> DefaultMemoryImpl {
+    STABLE_MEMORY.with(|memory| memory.clone())
+}
+
+// Virtual memory region handed out by the memory manager
+pub fn memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|manager| manager.borrow().get(id))
+}
+
+// Stores any serde value in stable structures as CBOR, which fills fields
+// missing from older records with their serde defaults. A record that cannot
+// be decoded traps, rolling the call back rather than losing the entry.
+pub struct Cbor<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned> Storable for Cbor<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        #[cfg(test)]
+        VALUES_CODED.with(|count| count.set(count.get() + 1));
+        Cow::Owned(serde_cbor::to_vec(&self.0).expect("failed to encode stable value"))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        #[cfg(test)]
+        VALUES_CODED.with(|count| count.set(count.get() + 1));
+        Cbor(serde_cbor::from_slice(&bytes).expect("failed to decode stable value"))
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[cfg(test)]
+thread_local! {
+    static VALUES_CODED: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+// Stable map values encoded or decoded so far, to check what an update touches
+#[cfg(test)]
+pub fn values_coded() -> u64 {
+    VALUES_CODED.with(std::cell::Cell::get)
+}
+
+// A value type kept in its own StableBTreeMap region
+pub trait StableValue: Serialize + DeserializeOwned + Clone {
+    const MEMORY_ID: MemoryId;
+}
+
+type Stored<V> = StableBTreeMap<String, Cbor<V>, Memory>;
+
+// Entries read from stable memory per range query while scanning
+const SCAN_BATCH: usize = 64;
+
+// Write-back view over a StableBTreeMap. Entries are read from stable memory
+// on demand and changes are buffered until flush, so an endpoint that returns
+// an error before saving leaves stable memory untouched.
+pub struct StableMap<V: StableValue> {
+    changes: HashMap<String, Option<V>>,
+}
+
+impl<V: StableValue> Default for StableMap<V> {
+    fn default() -> Self {
+        StableMap { changes: HashMap::new() }
+    }
+}
+
+impl<V: StableValue> StableMap<V> {
+    fn stored() -> Stored<V> {
+        StableBTreeMap::init(memory(V::MEMORY_ID))
+    }
+
+    fn load(&mut self, key: &str) {
+        if !self.changes.contains_key(key) {
+            let stored = Self::stored().get(&key.to_string()).map(|value| value.0);
+            self.changes.insert(key.to_string(), stored);
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        match self.changes.get(key) {
+            Some(change) => change.clone(),
+            None => Self::stored().get(&key.to_string()).map(|value| value.0),
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        match self.changes.get(key) {
+            Some(change) => change.is_some(),
+            None => Self::stored().contains_key(&key.to_string()),
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut V> {
+        self.load(key);
+        self.changes.get_mut(key).unwrap().as_mut()
+    }
+
+    pub fn get_mut_or_default(&mut self, key: &str) -> &mut V
+    where
+        V: Default,
+    {
+        self.load(key);
+        self.changes.get_mut(key).unwrap().get_or_insert_with(V::default)
+    }
+
+    pub fn insert(&mut self, key: String, value: V) {
+        self.changes.insert(key, Some(value));
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<V> {
+        let previous = self.get(key);
+        self.changes.insert(key.to_string(), None);
+        previous
+    }
+
+    // Scan in key order, including buffered changes. Stored entries are read
+    // lazily a batch at a time, so stopping early reads only what was used.
+    pub fn iter(&self) -> Iter<'_, V> {
+        let mut changes: Vec<(&String, &Option<V>)> = self.changes.iter().collect();
+        changes.sort_by(|a, b| a.0.cmp(b.0));
+        Iter {
+            stored: StoredIter { map: Self::stored(), batch: VecDeque::new(), last_key: None, exhausted: false }.peekable(),
+            changes: changes.into_iter().peekable(),
+        }
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = V> + '_ {
+        self.iter().map(|(_, value)| value)
+    }
+
+    // Write buffered changes through to stable memory
+    pub fn flush(&mut self) {
+        let mut map = Self::stored();
+        for (key, change) in std::mem::take(&mut self.changes) {
+            match change {
+                Some(value) => {
+                    map.insert(key, Cbor(value));
+                }
+                None => {
+                    map.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+// Stored entries in key order. Each batch is a fresh range query after the
+// last key seen, so no borrow of the map is held between calls.
+struct StoredIter<V: StableValue> {
+    map: Stored<V>,
+    batch: VecDeque<(String, V)>,
+    last_key: Option<String>,
+    exhausted: bool,
+}
+
+impl<V: StableValue> Iterator for StoredIter<V> {
+    type Item = (String, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.batch.is_empty() && !self.exhausted {
+            let start = match self.last_key.take() {
+                Some(key) => RangeBound::Excluded(key),
+                None => RangeBound::Unbounded,
+            };
+            self.batch = self.map.range((start, RangeBound::Unbounded))
+                .take(SCAN_BATCH)
+                .map(|(key, value)| (key, value.0))
+                .collect();
+            self.exhausted = self.batch.len() < SCAN_BATCH;
+            self.last_key = self.batch.back().map(|(key, _)| key.clone());
+        }
+        self.batch.pop_front()
+    }
+}
+
+// Stored entries merged with buffered changes, which take precedence
+pub struct Iter<'a, V: StableValue> {
+    stored: Peekable<StoredIter<V>>,
+    changes: Peekable<std::vec::IntoIter<(&'a String, &'a Option<V>)>>,
+}
+
+impl<V: StableValue> Iterator for Iter<'_, V> {
+    type Item = (String, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let order = match (self.stored.peek(), self.changes.peek()) {
+                (None, None) => return None,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((stored_key, _)), Some((changed_key, _))) => stored_key.cmp(*changed_key),
+            };
+            if order == Ordering::Equal {
+                self.stored.next(); // Superseded by the buffered change
+            }
+            if order == Ordering::Less {
+                return self.stored.next();
+            }
+            let (key, change) = self.changes.next().unwrap();
+            if let Some(value) = change {
+                return Some((key.clone(), value.clone()));
+            }
+        }
+    }
+}
+
+// The map's entries live in their own region, so inside the persisted state
+// record a handle is written as a unit and reads back as an empty view.
+impl<V: StableValue> Serialize for StableMap<V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+impl<'de, V: StableValue> Deserialize<'de> for StableMap<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IgnoredAny::deserialize(deserializer)?;
+        Ok(StableMap::default())
+    }
+}
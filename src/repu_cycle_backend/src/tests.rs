@@ -0,0 +1,2962 @@
+use super::*;
+
+fn register(id: &str) {
+    register_user(id.to_string(), format!("{} Example", id), format!("{}@example.com", id), "password".to_string(), None, "en".to_string(), None)
+        .unwrap();
+}
+
+// Caller principal standing in for a user's wallet
+fn principal_of(id: &str) -> Principal {
+    Principal::from_slice(id.as_bytes())
+}
+
+// Claim the first admin account from that admin's principal, which stays the caller
+fn claim_admin(id: &str) {
+    env::set_caller(principal_of(id));
+    claim_first_admin(id.to_string(), format!("{} Example", id), format!("{}@example.com", id), "password".to_string(), "en".to_string())
+        .unwrap();
+}
+
+// Footprint history (synth-301)
+
+#[test]
+fn footprint_history_is_kept_in_recording_order() {
+    register("alice");
+    for (waste, recyclable) in [(10, 2), (10, 5), (10, 8)] {
+        record_footprint("alice".to_string(), waste, recyclable).unwrap();
+        env::advance_secs(60);
+    }
+
+    let history = get_footprint_history("alice".to_string());
+    let scores: Vec<f32> = history.iter().map(|(_, footprint)| footprint.footprint_score).collect();
+    assert_eq!(scores, vec![20.0, 50.0, 80.0]);
+    assert!(history.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(get_latest_footprint("alice".to_string()).unwrap().footprint_score, 80.0);
+}
+
+#[test]
+fn footprint_history_is_empty_for_a_new_user() {
+    register("bob");
+    assert!(get_footprint_history("bob".to_string()).is_empty());
+    assert!(get_latest_footprint("bob".to_string()).is_none());
+}
+
+// Candid interface (synth-311)
+
+#[test]
+fn candid_interface_matches_did_file() {
+    let generated = __export_service();
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/repu_cycle_backend.did");
+    if std::env::var_os("UPDATE_CANDID").is_some() {
+        std::fs::write(path, &generated).unwrap();
+    }
+    let committed = std::fs::read_to_string(path).unwrap();
+    assert!(committed == generated, "repu_cycle_backend.did is stale; run `npm run generate` or rerun this test with UPDATE_CANDID=1");
+}
+
+#[test]
+fn candid_interface_exports_public_types() {
+    let generated = __export_service();
+    for name in ["UserProfile", "LeaderboardEntry", "AppError"] {
+        assert!(generated.contains(&format!("type {} = ", name)), "{} is not exported", name);
+    }
+    for method in ["register_user", "submit_quiz", "icrc1_transfer", "get_leaderboard_around_me"] {
+        assert!(generated.contains(&format!("  {} : (", method)), "{} is not exported", method);
+    }
+}
+
+// Storage layout and upgrades (synth-312)
+
+fn baseline_snapshot() -> legacy::Snapshot {
+    let mut users = HashMap::new();
+    users.insert("alice".to_string(), legacy::User {
+        id: "alice".to_string(),
+        full_name: "Alice Example".to_string(),
+        email: "Alice@Example.com".to_string(),
+        hashed_password: "0f".repeat(32),
+        tokens: 120,
+        role: legacy::Role::Admin,
+        preferred_language: "en".to_string(),
+        session_token: Some(legacy::SessionToken { token: "t".to_string(), expires_at: 1 }),
+        achievements: vec!["First Steps".to_string()],
+        challenges_completed: vec![],
+        completed_courses: vec!["Recycling 101".to_string()],
+        passed_quizzes: vec!["Sorting".to_string()],
+        notifications: vec![],
+    });
+    let mut footprints = HashMap::new();
+    footprints.insert("alice".to_string(), legacy::UserFootprint { waste_generated: 10, recyclable_waste: 4, footprint_score: 40.0 });
+    let mut levels = HashMap::new();
+    levels.insert(1, legacy::Quiz {
+        level: 1,
+        questions: vec!["Is glass recyclable?".to_string()],
+        options: vec![vec!["Yes".to_string(), "No".to_string()]],
+        correct_answers: vec!["Yes".to_string()],
+        reward: 10,
+    });
+    let mut courses = HashMap::new();
+    courses.insert("Recycling 101".to_string(), legacy::Course {
+        title: "Recycling 101".to_string(),
+        levels,
+        educational_resources: vec!["https://example.com/recycling".to_string()],
+    });
+    let mut notifications = HashMap::new();
+    notifications.insert("alice".to_string(), vec![legacy::Notification {
+        user_id: "alice".to_string(),
+        message: "New course added".to_string(),
+        timestamp: 5,
+        notification_type: legacy::NotificationType::CourseAdded,
+    }]);
+    let mut rewards = HashMap::new();
+    rewards.insert("r1".to_string(), legacy::Reward { id: "r1".to_string(), description: "Tote bag".to_string(), cost_tokens: 50 });
+    (
+        users,
+        footprints,
+        HashMap::new(),
+        HashMap::new(),
+        HashMap::new(),
+        vec!["{\"action\":\"User registered: alice\"}".to_string()],
+        courses,
+        notifications,
+        vec![legacy::Feedback { user_id: "alice".to_string(), feedback: "Great app".to_string() }],
+        rewards,
+    )
+}
+
+// Lay stable memory out the way the baseline's stable_save did
+fn write_baseline(snapshot: legacy::Snapshot) {
+    let bytes = candid::encode_args(snapshot).unwrap();
+    let memory = stable_memory();
+    memory.grow(1);
+    memory.write(0, &bytes);
+}
+
+#[test]
+fn upgrade_from_baseline_carries_collections_over() {
+    write_baseline(baseline_snapshot());
+
+    migrate_storage();
+
+    let state = restore_storage();
+    assert_eq!(state.schema_version, SCHEMA_VERSION);
+    let alice = state.users.get("alice").unwrap();
+    assert_eq!(alice.tokens, 120);
+    assert_eq!(alice.role, Role::Admin);
+    assert_eq!(alice.passed_quizzes, vec!["Sorting".to_string()]);
+    assert!(alice.sessions.is_empty());
+    assert_eq!(state.user_count, 1);
+    assert_eq!(state.email_index.get("alice@example.com"), Some(&"alice".to_string()));
+    assert_eq!(state.footprints["alice"].len(), 1);
+    assert_eq!(state.footprints["alice"][0].1.footprint_score, 40.0);
+    let course = state.courses.get("Recycling 101").unwrap();
+    assert_eq!(course.levels[&1].reward, 10);
+    assert_eq!(course.levels[&1].pass_threshold, default_pass_threshold());
+    let notifications = state.notifications.get("alice").unwrap();
+    assert!(notifications[0].notification_type == NotificationType::CourseAdded);
+    assert!(!notifications[0].read);
+    assert_eq!(state.feedbacks.len(), 1);
+    assert!(!state.feedbacks[0].id.is_empty());
+    assert_eq!(state.rewards["r1"].cost_tokens, 50);
+    assert_eq!(state.log.len(), 1);
+}
+
+#[test]
+fn upgrade_after_migration_keeps_current_state() {
+    write_baseline(baseline_snapshot());
+    migrate_storage();
+    register("bob");
+
+    migrate_storage();
+
+    let state = restore_storage();
+    assert!(state.users.contains_key("alice"));
+    assert!(state.users.contains_key("bob"));
+    assert_eq!(state.user_count, 2);
+}
+
+#[test]
+fn state_record_missing_newer_fields_takes_defaults() {
+    let mut older = HashMap::new();
+    older.insert("user_count", 3u32);
+    let bytes = serde_cbor::to_vec(&older).unwrap();
+
+    let state: State = serde_cbor::from_slice(&bytes).unwrap();
+
+    assert_eq!(state.user_count, 3);
+    assert_eq!(state.schema_version, 0);
+    assert_eq!(state.settings.max_notifications, Settings::default().max_notifications);
+    assert!(state.teams.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "newer than")]
+fn state_from_a_newer_schema_is_refused() {
+    let state = State { schema_version: SCHEMA_VERSION + 1, ..State::default() };
+    let bytes = serde_cbor::to_vec(&state).unwrap();
+    STATE.with(|cell| cell.borrow_mut().set(bytes)).unwrap();
+
+    check_schema_version();
+}
+
+#[test]
+fn iteration_merges_buffered_changes_in_key_order() {
+    for id in ["carol", "alice", "erin"] {
+        register(id);
+    }
+    let mut state = restore_storage();
+    state.users.remove("carol");
+    state.users.insert("bob".to_string(), state.users.get("alice").unwrap());
+    state.users.get_mut("erin").unwrap().tokens = 7;
+
+    let ids: Vec<String> = state.users.iter().map(|(id, _)| id).collect();
+    assert_eq!(ids, vec!["alice", "bob", "erin"]);
+    assert_eq!(state.users.get("erin").unwrap().tokens, 7);
+    assert_eq!(state.users.values().find(|user| user.id == "erin").unwrap().tokens, 7);
+}
+
+#[test]
+fn iteration_pages_through_more_users_than_one_batch() {
+    for index in 0..150 {
+        register(&format!("user{:03}", index));
+    }
+
+    let state = restore_storage();
+    let ids: Vec<String> = state.users.iter().map(|(id, _)| id).collect();
+    assert_eq!(ids.len(), 150);
+    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+    assert_eq!(state.users.iter().take(3).count(), 3);
+}
+
+#[test]
+fn single_user_update_does_not_scale_with_user_count() {
+    fn values_coded_by_login() -> u64 {
+        let before = stable_map::values_coded();
+        login_user("user000".to_string(), "password".to_string(), "web".to_string()).unwrap();
+        stable_map::values_coded() - before
+    }
+
+    for index in 0..5 {
+        register(&format!("user{:03}", index));
+    }
+    let with_few_users = values_coded_by_login();
+    for index in 5..200 {
+        register(&format!("user{:03}", index));
+    }
+    let with_many_users = values_coded_by_login();
+
+    assert_eq!(with_few_users, with_many_users);
+}
+
+// Timed quizzes (synth-292)
+
+// Added by "admin", which the test has claimed
+fn add_timed_quiz(title: &str, time_limit_secs: u64) {
+    add_quiz(
+        "admin".to_string(),
+        title.to_string(),
+        1,
+        vec!["Is glass recyclable?".to_string()],
+        vec![vec!["Yes".to_string(), "No".to_string()]],
+        vec!["Yes".to_string()],
+        10,
+        time_limit_secs,
+        100,
+        0,
+        Difficulty::Easy,
+    )
+    .unwrap();
+}
+
+fn quiz_attempts(user_id: &str, quiz_title: &str) -> u32 {
+    restore_storage().users.get(user_id).unwrap().quiz_attempts.get(quiz_title).copied().unwrap_or(0)
+}
+
+#[test]
+fn timed_quiz_cannot_be_restarted_while_running() {
+    claim_admin("admin");
+    register("alice");
+    add_timed_quiz("Sorting", 60);
+    start_quiz("alice".to_string(), "Sorting".to_string(), false).unwrap();
+    env::advance_secs(30);
+
+    let result = start_quiz("alice".to_string(), "Sorting".to_string(), false);
+
+    assert!(matches!(result, Err(AppError::QuizAlreadyStarted)));
+}
+
+#[test]
+fn restarting_after_a_timed_out_start_counts_an_attempt() {
+    claim_admin("admin");
+    register("alice");
+    add_timed_quiz("Sorting", 60);
+    start_quiz("alice".to_string(), "Sorting".to_string(), false).unwrap();
+    env::advance_secs(61);
+
+    start_quiz("alice".to_string(), "Sorting".to_string(), false).unwrap();
+
+    assert_eq!(quiz_attempts("alice", "Sorting"), 1);
+}
+
+#[test]
+fn submitting_after_the_time_limit_counts_an_attempt() {
+    claim_admin("admin");
+    register("alice");
+    add_timed_quiz("Sorting", 60);
+    start_quiz("alice".to_string(), "Sorting".to_string(), false).unwrap();
+    env::advance_secs(61);
+
+    let result = submit_quiz("alice".to_string(), "Sorting".to_string(), vec!["Yes".to_string()]);
+
+    assert!(matches!(result, Err(AppError::QuizTimedOut)));
+    assert_eq!(quiz_attempts("alice", "Sorting"), 1);
+    assert!(!restore_storage().users.get("alice").unwrap().passed_quizzes.contains(&"Sorting".to_string()));
+}
+
+#[test]
+fn submitting_within_the_time_limit_passes() {
+    claim_admin("admin");
+    register("alice");
+    add_timed_quiz("Sorting", 60);
+    start_quiz("alice".to_string(), "Sorting".to_string(), false).unwrap();
+    env::advance_secs(59);
+
+    let result = submit_quiz("alice".to_string(), "Sorting".to_string(), vec!["Yes".to_string()]).unwrap();
+
+    assert!(result.passed);
+    assert_eq!(quiz_attempts("alice", "Sorting"), 1);
+}
+
+#[test]
+fn submitting_a_timed_quiz_requires_a_start() {
+    claim_admin("admin");
+    register("alice");
+    add_timed_quiz("Sorting", 60);
+
+    let result = submit_quiz("alice".to_string(), "Sorting".to_string(), vec!["Yes".to_string()]);
+
+    assert!(matches!(result, Err(AppError::QuizNotStarted)));
+}
+
+#[test]
+fn edit_quiz_rejects_overlong_text() {
+    claim_admin("admin");
+    add_timed_quiz("Sorting", 0);
+
+    let result = edit_quiz(
+        "admin".to_string(),
+        "Sorting".to_string(),
+        vec!["q".repeat(MAX_QUIZ_TEXT_LEN + 1)],
+        vec![vec!["Yes".to_string(), "No".to_string()]],
+        vec!["Yes".to_string()],
+        10,
+    );
+
+    assert!(matches!(result, Err(AppError::InputTooLong(_))));
+}
+
+#[test]
+fn claim_first_admin_validates_input_before_lookups() {
+    claim_admin("admin");
+
+    let result = claim_first_admin("x".repeat(MAX_ID_LEN + 1), "Admin".to_string(), "other@example.com".to_string(), "password".to_string(), "en".to_string());
+
+    assert!(matches!(result, Err(AppError::InputTooLong(_))));
+}
+
+// Admin authentication (synth-293)
+
+#[test]
+fn admin_calls_are_accepted_from_the_admins_principal() {
+    claim_admin("admin");
+    register("alice");
+
+    assert!(set_user_role("admin".to_string(), "alice".to_string(), Role::Admin).is_ok());
+}
+
+#[test]
+fn admin_calls_naming_an_admin_from_another_principal_are_rejected() {
+    claim_admin("admin");
+    register("alice");
+    env::set_caller(principal_of("mallory"));
+
+    let result = set_user_role("admin".to_string(), "alice".to_string(), Role::Admin);
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn admin_calls_from_the_anonymous_principal_are_rejected() {
+    claim_admin("admin");
+    register("alice");
+    env::set_caller(Principal::anonymous());
+
+    let result = set_user_role("admin".to_string(), "alice".to_string(), Role::Admin);
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn first_admin_cannot_be_claimed_anonymously() {
+    let result = claim_first_admin("admin".to_string(), "Admin".to_string(), "admin@example.com".to_string(), "password".to_string(), "en".to_string());
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn promoted_admin_acts_after_linking_a_principal() {
+    claim_admin("admin");
+    register("alice");
+    set_user_role("admin".to_string(), "alice".to_string(), Role::Admin).unwrap();
+    env::set_caller(principal_of("alice"));
+    assert!(matches!(suspend_user("alice".to_string(), "admin".to_string()), Err(AppError::PrincipalNotLinked)));
+
+    link_principal("alice".to_string(), "password".to_string()).unwrap();
+
+    assert!(set_user_role("alice".to_string(), "admin".to_string(), Role::Admin).is_ok());
+}
+
+// Quiz administration (synth-291)
+
+fn add_quiz_as(admin_id: &str, title: &str, reward: u32) -> Result<String, AppError> {
+    add_quiz(
+        admin_id.to_string(),
+        title.to_string(),
+        1,
+        vec!["Is glass recyclable?".to_string()],
+        vec![vec!["Yes".to_string(), "No".to_string()]],
+        vec!["Yes".to_string()],
+        reward,
+        0,
+        100,
+        0,
+        Difficulty::Easy,
+    )
+}
+
+#[test]
+fn add_quiz_requires_an_admin() {
+    claim_admin("admin");
+    register("alice");
+
+    assert!(matches!(add_quiz_as("alice", "Sorting", 10), Err(AppError::Unauthorized)));
+    assert!(add_quiz_as("admin", "Sorting", 10).is_ok());
+}
+
+#[test]
+fn quiz_rewards_are_capped_by_settings() {
+    claim_admin("admin");
+    let cap = Settings::default().max_quiz_reward;
+
+    assert!(matches!(add_quiz_as("admin", "Sorting", cap + 1), Err(AppError::RewardTooLarge)));
+    add_quiz_as("admin", "Sorting", cap).unwrap();
+
+    let result = edit_quiz(
+        "admin".to_string(),
+        "Sorting".to_string(),
+        vec!["Is glass recyclable?".to_string()],
+        vec![vec!["Yes".to_string(), "No".to_string()]],
+        vec!["Yes".to_string()],
+        cap + 1,
+    );
+    assert!(matches!(result, Err(AppError::RewardTooLarge)));
+}
+
+#[test]
+fn course_levels_are_held_to_the_quiz_rules() {
+    claim_admin("admin");
+    let cap = Settings::default().max_quiz_reward;
+    let add = |quiz: Quiz| add_course("admin".to_string(), "Recycling 101".to_string(), HashMap::from([(1, quiz)]), vec![], vec![], 0);
+
+    assert!(matches!(add(graded_quiz(1, 70, 0, Difficulty::Easy, cap + 1)), Err(AppError::RewardTooLarge)));
+    let unanswered = Quiz { correct_answers: vec![], ..graded_quiz(1, 70, 0, Difficulty::Easy, 10) };
+    assert!(matches!(add(unanswered), Err(AppError::InvalidQuizStructure)));
+    assert!(matches!(add(graded_quiz(1, 0, 0, Difficulty::Easy, 10)), Err(AppError::InvalidPassThreshold)));
+    assert!(!restore_storage().courses.contains_key("Recycling 101"));
+
+    add(graded_quiz(1, 70, 0, Difficulty::Easy, cap)).unwrap();
+}
+
+#[test]
+fn quiz_attempt_limit_comes_from_settings() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz_as("admin", "Sorting", 10).unwrap();
+    set_quiz_limits("admin".to_string(), 1, 100).unwrap();
+
+    submit_quiz("alice".to_string(), "Sorting".to_string(), vec!["No".to_string()]).unwrap();
+    let result = submit_quiz("alice".to_string(), "Sorting".to_string(), vec!["Yes".to_string()]);
+
+    assert!(matches!(result, Err(AppError::MaxAttemptsReached)));
+}
+
+#[test]
+fn quiz_attempt_limit_cannot_be_zero() {
+    claim_admin("admin");
+
+    let result = set_quiz_limits("admin".to_string(), 0, 100);
+
+    assert!(matches!(result, Err(AppError::InvalidSetting(_))));
+}
+
+// Challenge and course administration (synth-360)
+
+fn add_challenge_as(admin_id: &str, reward_tokens: u32) -> Result<String, AppError> {
+    add_challenge(admin_id.to_string(), "Collect 10 bottles".to_string(), reward_tokens, vec![], vec![], false, None, "Plastic".to_string(), None)
+}
+
+#[test]
+fn challenge_reward_within_the_cap_is_accepted() {
+    claim_admin("admin");
+
+    assert!(add_challenge_as("admin", Settings::default().max_challenge_reward).is_ok());
+    assert_eq!(restore_storage().challenges.len(), 1);
+}
+
+#[test]
+fn challenge_reward_above_the_cap_is_rejected() {
+    claim_admin("admin");
+    set_max_challenge_reward("admin".to_string(), 50).unwrap();
+
+    assert!(matches!(add_challenge_as("admin", 51), Err(AppError::RewardTooLarge)));
+    assert!(restore_storage().challenges.is_empty());
+}
+
+#[test]
+fn add_challenge_requires_an_admin() {
+    claim_admin("admin");
+    register("alice");
+
+    assert!(matches!(add_challenge_as("alice", 10), Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn add_course_requires_an_admin() {
+    claim_admin("admin");
+    register("alice");
+
+    let result = add_course("alice".to_string(), "Recycling 101".to_string(), HashMap::new(), vec![], vec![], 0);
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+
+    add_course("admin".to_string(), "Recycling 101".to_string(), HashMap::new(), vec![], vec![], 0).unwrap();
+    assert!(restore_storage().courses.contains_key("Recycling 101"));
+}
+
+// Leaderboard by role (synth-298)
+
+fn set_tokens(user_id: &str, tokens: u32) {
+    let mut state = restore_storage();
+    state.users.get_mut(user_id).unwrap().tokens = tokens;
+    save_storage(state).unwrap();
+}
+
+#[test]
+fn user_leaderboard_excludes_admins_and_pages_in_token_order() {
+    claim_admin("admin");
+    for (id, tokens) in [("alice", 30), ("bob", 50), ("carol", 40)] {
+        register(id);
+        set_tokens(id, tokens);
+    }
+    set_tokens("admin", 100);
+    env::set_caller(Principal::anonymous());
+
+    let page = get_leaderboard_by_role(None, Some(Role::User), 1, 2).unwrap();
+
+    assert_eq!(page, vec![("carol".to_string(), 40), ("alice".to_string(), 30)]);
+}
+
+#[test]
+fn admin_leaderboard_is_only_shown_to_admins() {
+    claim_admin("admin");
+    register("alice");
+
+    let admins = get_leaderboard_by_role(Some("admin".to_string()), Some(Role::Admin), 0, 10).unwrap();
+    assert_eq!(admins.iter().map(|(id, _)| id.as_str()).collect::<Vec<_>>(), vec!["admin"]);
+
+    env::set_caller(principal_of("alice"));
+    assert!(matches!(get_leaderboard_by_role(None, Some(Role::Admin), 0, 10), Err(AppError::Unauthorized)));
+    assert!(matches!(get_leaderboard_by_role(Some("alice".to_string()), None, 0, 10), Err(AppError::Unauthorized)));
+}
+
+// Password reset (synth-308)
+
+#[test]
+fn password_reset_token_is_not_returned_to_the_requester() {
+    claim_admin("admin");
+    register("alice");
+
+    let reply = request_password_reset("alice@example.com".to_string()).unwrap();
+    let token = get_password_reset_token("admin".to_string(), "alice".to_string()).unwrap().unwrap();
+
+    assert!(!reply.contains(&token));
+    assert_eq!(reply, request_password_reset("nobody@example.com".to_string()).unwrap());
+    reset_password(token, "new password".to_string()).unwrap();
+    assert!(login_user("alice".to_string(), "new password".to_string(), "web".to_string()).is_ok());
+}
+
+#[test]
+fn password_reset_token_is_only_given_to_admins() {
+    claim_admin("admin");
+    register("alice");
+    request_password_reset("alice@example.com".to_string()).unwrap();
+    env::set_caller(principal_of("alice"));
+
+    let result = get_password_reset_token("alice".to_string(), "alice".to_string());
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+}
+
+// Logging out (synth-310)
+
+fn login(user_id: &str, device_id: &str) -> String {
+    login_user(user_id.to_string(), "password".to_string(), device_id.to_string()).unwrap()
+}
+
+fn session_count(user_id: &str) -> usize {
+    restore_storage().users.get(user_id).unwrap().sessions.len()
+}
+
+#[test]
+fn logout_requires_a_session_token() {
+    register("alice");
+    login("alice", "phone");
+
+    let result = logout("alice".to_string(), "forged".to_string(), "phone".to_string());
+
+    assert!(matches!(result, Err(AppError::SessionTokenExpired)));
+    assert_eq!(session_count("alice"), 1);
+}
+
+#[test]
+fn logout_ends_only_the_named_device() {
+    register("alice");
+    let token = login("alice", "phone");
+    login("alice", "laptop");
+
+    logout("alice".to_string(), token, "phone".to_string()).unwrap();
+
+    let sessions = restore_storage().users.get("alice").unwrap().sessions;
+    assert_eq!(sessions.iter().map(|session| session.device_id.as_str()).collect::<Vec<_>>(), vec!["laptop"]);
+}
+
+#[test]
+fn logout_all_requires_a_session_token() {
+    register("alice");
+    let token = login("alice", "phone");
+    login("alice", "laptop");
+
+    assert!(matches!(logout_all("alice".to_string(), "forged".to_string()), Err(AppError::SessionTokenExpired)));
+    assert_eq!(session_count("alice"), 2);
+
+    logout_all("alice".to_string(), token).unwrap();
+    assert_eq!(session_count("alice"), 0);
+}
+
+// Teams (synth-304)
+
+fn team_of(owner: &str, invitees: &[&str]) -> (String, String) {
+    let token = login(owner, "web");
+    let team_id = create_team(owner.to_string(), token.clone(), "Green Team".to_string(), invitees.iter().map(|id| id.to_string()).collect()).unwrap();
+    (team_id, token)
+}
+
+fn team(team_id: &str) -> Team {
+    get_team(team_id.to_string()).unwrap()
+}
+
+#[test]
+fn invited_users_join_a_team_only_by_accepting() {
+    register("alice");
+    register("bob");
+    let (team_id, _) = team_of("alice", &["bob"]);
+    assert_eq!(team(&team_id).members, vec!["alice".to_string()]);
+    assert_eq!(team(&team_id).invited, vec!["bob".to_string()]);
+
+    accept_team_invite("bob".to_string(), login("bob", "web"), team_id.clone()).unwrap();
+
+    assert_eq!(team(&team_id).members, vec!["alice".to_string(), "bob".to_string()]);
+    assert!(team(&team_id).invited.is_empty());
+}
+
+#[test]
+fn uninvited_users_cannot_join_a_team() {
+    register("alice");
+    register("carol");
+    let (team_id, _) = team_of("alice", &[]);
+
+    let result = accept_team_invite("carol".to_string(), login("carol", "web"), team_id);
+
+    assert!(matches!(result, Err(AppError::NotInvited)));
+}
+
+#[test]
+fn team_creation_requires_a_session() {
+    register("alice");
+
+    let result = create_team("alice".to_string(), "forged".to_string(), "Green Team".to_string(), vec![]);
+
+    assert!(matches!(result, Err(AppError::SessionTokenExpired)));
+}
+
+#[test]
+fn only_the_owner_or_an_admin_manages_a_team() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    let (team_id, owner_token) = team_of("alice", &["bob"]);
+    let bob_token = login("bob", "web");
+    accept_team_invite("bob".to_string(), bob_token.clone(), team_id.clone()).unwrap();
+
+    let result = update_team("bob".to_string(), bob_token.clone(), team_id.clone(), "Bob's Team".to_string(), vec!["bob".to_string()]);
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+    assert!(matches!(delete_team("bob".to_string(), bob_token, team_id.clone()), Err(AppError::Unauthorized)));
+
+    update_team("alice".to_string(), owner_token, team_id.clone(), "Renamed".to_string(), vec![]).unwrap();
+    assert_eq!(team(&team_id).name, "Renamed");
+    assert_eq!(team(&team_id).members, vec!["alice".to_string()]);
+
+    delete_team("admin".to_string(), String::new(), team_id.clone()).unwrap();
+    assert!(get_team(team_id).is_none());
+}
+
+#[test]
+fn team_completes_a_challenge_and_splits_the_reward() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    let (team_id, _) = team_of("alice", &["bob"]);
+    accept_team_invite("bob".to_string(), login("bob", "web"), team_id).unwrap();
+    add_challenge("admin".to_string(), "Team cleanup".to_string(), 101, vec![], vec![], true, None, "Plastic".to_string(), None).unwrap();
+    let challenge_id = restore_storage().challenges.keys().next().unwrap().clone();
+    let before: Vec<u32> = ["alice", "bob"].iter().map(|id| restore_storage().users.get(id).unwrap().tokens).collect();
+
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+
+    let state = restore_storage();
+    let alice = state.users.get("alice").unwrap();
+    let bob = state.users.get("bob").unwrap();
+    assert_eq!(alice.tokens - before[0], 51); // The joining member also gets the remainder
+    assert_eq!(bob.tokens - before[1], 50);
+    assert!(bob.challenges_completed.contains(&challenge_id));
+    assert_eq!(state.challenges[&challenge_id].participants.len(), 2);
+}
+
+// Cash-outs (synth-347)
+
+// Run an async endpoint that is expected to return before its first call out
+fn poll_once<F: std::future::Future>(future: F) -> F::Output {
+    let mut context = std::task::Context::from_waker(std::task::Waker::noop());
+    match std::pin::pin!(future).poll(&mut context) {
+        std::task::Poll::Ready(output) => output,
+        std::task::Poll::Pending => panic!("endpoint made an inter-canister call"),
+    }
+}
+
+// Alice holding 100 tokens with 40 of them out in a pending cash-out
+fn pending_cash_out() -> String {
+    register("alice");
+    let mut state = restore_storage();
+    state.users.get_mut("alice").unwrap().tokens = 60;
+    state.pending_cash_outs.insert("c1".to_string(), PendingCashOut {
+        id: "c1".to_string(),
+        user_id: "alice".to_string(),
+        amount: 40,
+        to: principal_of("alice"),
+        created_at_time: env::time(),
+    });
+    save_storage(state).unwrap();
+    "c1".to_string()
+}
+
+fn settle(cash_out_id: &str, outcome: TransferOutcome) -> Result<u128, AppError> {
+    let mut state = restore_storage();
+    let result = settle_cash_out(&mut state, cash_out_id, &outcome);
+    save_storage(state).unwrap();
+    result
+}
+
+fn tokens_of(user_id: &str) -> u32 {
+    restore_storage().users.get(user_id).unwrap().tokens
+}
+
+#[test]
+fn completed_cash_out_keeps_the_held_tokens() {
+    let id = pending_cash_out();
+
+    assert_eq!(settle(&id, transfer_outcome(Ok((Ok(7),)))).unwrap(), 7);
+
+    assert_eq!(tokens_of("alice"), 60);
+    assert!(restore_storage().pending_cash_outs.is_empty());
+    assert_eq!(restore_storage().token_history["alice"].last().unwrap().delta, -40);
+}
+
+#[test]
+fn duplicate_transfer_counts_as_completed() {
+    let id = pending_cash_out();
+
+    let outcome = transfer_outcome(Ok((Err(TransferError::Duplicate { duplicate_of: 3 }),)));
+
+    assert_eq!(settle(&id, outcome).unwrap(), 3);
+    assert_eq!(tokens_of("alice"), 60);
+}
+
+#[test]
+fn ledger_rejection_refunds_the_held_tokens() {
+    let id = pending_cash_out();
+
+    let outcome = transfer_outcome(Ok((Err(TransferError::InsufficientFunds { balance: 0 }),)));
+
+    assert!(matches!(settle(&id, outcome), Err(AppError::LedgerError(_))));
+    assert_eq!(tokens_of("alice"), 100);
+    assert!(restore_storage().pending_cash_outs.is_empty());
+}
+
+#[test]
+fn undelivered_call_refunds_the_held_tokens() {
+    let id = pending_cash_out();
+
+    let outcome = transfer_outcome(Err((RejectionCode::SysTransient, "queue full".to_string())));
+
+    assert!(matches!(settle(&id, outcome), Err(AppError::LedgerError(_))));
+    assert_eq!(tokens_of("alice"), 100);
+}
+
+#[test]
+fn unknown_outcome_is_held_for_reconciliation() {
+    claim_admin("admin");
+    let id = pending_cash_out();
+
+    let outcome = transfer_outcome(Err((RejectionCode::CanisterError, "trapped".to_string())));
+
+    assert!(matches!(settle(&id, outcome), Err(AppError::CashOutPending(_))));
+    assert_eq!(tokens_of("alice"), 60);
+    assert_eq!(get_pending_cash_outs("admin".to_string()).unwrap().len(), 1);
+
+    resolve_cash_out("admin".to_string(), id, None).unwrap();
+    assert_eq!(tokens_of("alice"), 100);
+    assert!(get_pending_cash_outs("admin".to_string()).unwrap().is_empty());
+}
+
+#[test]
+fn cash_out_must_come_from_the_linked_principal() {
+    claim_admin("admin");
+    set_ledger_canister("admin".to_string(), Some(principal_of("ledger"))).unwrap();
+    register("alice");
+    env::set_caller(principal_of("alice"));
+    link_principal("alice".to_string(), "password".to_string()).unwrap();
+    env::set_caller(principal_of("mallory"));
+
+    let result = poll_once(cash_out("alice".to_string(), 5, None));
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+}
+
+// ICRC-1 transfers (synth-346)
+
+// Alice with 100 tokens, linked to her principal, which stays the caller
+fn linked_sender() {
+    register("alice");
+    register("bob");
+    set_tokens("alice", 100);
+    env::set_caller(principal_of("alice"));
+    link_principal("alice".to_string(), "password".to_string()).unwrap();
+}
+
+fn transfer_to_bob(amount: u128, memo: Option<Vec<u8>>, created_at_time: Option<u64>) -> Result<u128, TransferError> {
+    icrc1_transfer(TransferArg {
+        from_subaccount: None,
+        to: Account { owner: env::id(), subaccount: Some(user_subaccount("bob")) },
+        amount,
+        fee: None,
+        memo,
+        created_at_time,
+    })
+}
+
+fn balance_of(user_id: &str) -> u128 {
+    icrc1_balance_of(Account { owner: env::id(), subaccount: Some(user_subaccount(user_id)) })
+}
+
+#[test]
+fn standard_transfer_moves_balances() {
+    linked_sender();
+    let bob_before = balance_of("bob");
+
+    let block_index = transfer_to_bob(30, None, None).unwrap();
+
+    assert_eq!(block_index, 0);
+    assert_eq!(balance_of("alice"), 70);
+    assert_eq!(balance_of("bob"), bob_before + 30);
+}
+
+#[test]
+fn retried_transfer_is_reported_as_a_duplicate() {
+    linked_sender();
+    let created_at_time = env::time();
+    let block_index = transfer_to_bob(20, Some(vec![1]), Some(created_at_time)).unwrap();
+
+    let retry = transfer_to_bob(20, Some(vec![1]), Some(created_at_time));
+
+    assert!(matches!(retry, Err(TransferError::Duplicate { duplicate_of }) if duplicate_of == block_index));
+    assert_eq!(balance_of("alice"), 80);
+    assert!(transfer_to_bob(20, Some(vec![2]), Some(created_at_time + 1)).is_ok());
+    assert!(transfer_to_bob(20, None, None).is_ok());
+    assert!(transfer_to_bob(20, None, None).is_ok()); // Without created_at_time nothing is deduplicated
+}
+
+#[test]
+fn deduplication_window_entries_expire() {
+    linked_sender();
+    transfer_to_bob(10, None, Some(env::time())).unwrap();
+    assert_eq!(restore_storage().recent_transfer_hashes.len(), 1);
+
+    env::advance_secs((ICRC1_TX_WINDOW_NANOS + ICRC1_PERMITTED_DRIFT_NANOS) / 1_000_000_000 + 1);
+    transfer_to_bob(10, None, None).unwrap();
+
+    assert!(restore_storage().recent_transfer_hashes.is_empty());
+}
+
+#[test]
+fn created_at_time_far_in_the_future_does_not_overflow() {
+    linked_sender();
+
+    let result = transfer_to_bob(10, None, Some(u64::MAX));
+
+    assert!(matches!(result, Err(TransferError::CreatedInFuture { .. })));
+}
+
+// Replay protection (synth-388)
+
+// Alice, linked and verified, able to redeem a 10-token reward
+fn reward_redeemer() {
+    linked_sender();
+    let mut state = restore_storage();
+    state.users.get_mut("alice").unwrap().email_verified = true;
+    state.rewards.insert("r1".to_string(), Reward { id: "r1".to_string(), description: "Tote bag".to_string(), cost_tokens: 10, category: String::new() });
+    save_storage(state).unwrap();
+}
+
+#[test]
+fn increasing_nonces_are_accepted_and_replays_rejected() {
+    reward_redeemer();
+
+    redeem_reward("alice".to_string(), "r1".to_string(), Some(1)).unwrap();
+    redeem_reward("alice".to_string(), "r1".to_string(), Some(2)).unwrap();
+    let replay = redeem_reward("alice".to_string(), "r1".to_string(), Some(2));
+
+    assert!(matches!(replay, Err(AppError::ReplayedRequest)));
+    assert_eq!(tokens_of("alice"), 80);
+}
+
+#[test]
+fn nonces_are_only_accepted_from_the_users_principal() {
+    reward_redeemer();
+    env::set_caller(principal_of("mallory"));
+
+    let result = redeem_reward("alice".to_string(), "r1".to_string(), Some(1));
+
+    assert!(matches!(result, Err(AppError::Unauthorized)));
+    assert!(restore_storage().request_nonces.is_empty());
+}
+
+#[test]
+fn transfers_with_equal_or_earlier_timestamps_are_not_replays() {
+    linked_sender();
+    let created_at_time = env::time();
+    transfer_to_bob(10, None, Some(created_at_time)).unwrap();
+
+    assert!(transfer_to_bob(15, None, Some(created_at_time)).is_ok());
+    assert!(transfer_to_bob(15, None, Some(created_at_time - 1)).is_ok());
+    assert!(matches!(transfer_to_bob(10, None, Some(created_at_time)), Err(TransferError::Duplicate { duplicate_of: 0 })));
+    assert_eq!(balance_of("alice"), 60);
+}
+
+#[test]
+fn transfers_outside_the_window_are_rejected() {
+    linked_sender();
+    let too_old = env::time() - ICRC1_TX_WINDOW_NANOS - 1;
+
+    assert!(matches!(transfer_to_bob(10, None, Some(too_old)), Err(TransferError::TooOld)));
+    let too_new = env::time() + ICRC1_PERMITTED_DRIFT_NANOS + 1;
+    assert!(matches!(transfer_to_bob(10, None, Some(too_new)), Err(TransferError::CreatedInFuture { .. })));
+    assert_eq!(balance_of("alice"), 100);
+}
+
+// Notification templates (synth-339)
+
+const NOTIFICATION_TYPES: [NotificationType; 11] = [
+    NotificationType::CourseAdded,
+    NotificationType::ChallengeAdded,
+    NotificationType::ChallengeStarted,
+    NotificationType::ChallengeParticipated,
+    NotificationType::ChallengeReminder,
+    NotificationType::AchievementShared,
+    NotificationType::FeedbackResponse,
+    NotificationType::RewardRedeemed,
+    NotificationType::Announcement,
+    NotificationType::OnboardingComplete,
+    NotificationType::LowCycles,
+];
+
+#[test]
+fn course_added_renders_in_the_users_language() {
+    assert_eq!(render_notification(NotificationType::CourseAdded, "en", &["Composting"]), "New course available: Composting");
+    assert_eq!(render_notification(NotificationType::CourseAdded, "es", &["Composting"]), "Nuevo curso disponible: Composting");
+    assert_eq!(render_notification(NotificationType::CourseAdded, "es-MX", &["Composting"]), "Nuevo curso disponible: Composting");
+    assert_eq!(render_notification(NotificationType::CourseAdded, "de", &["Composting"]), "New course available: Composting");
+}
+
+#[test]
+fn every_template_exists_in_every_supported_language() {
+    let templates = notification_templates();
+    for notification_type in NOTIFICATION_TYPES {
+        let english = &templates[&(notification_type.clone(), DEFAULT_LANGUAGE.to_string())];
+        for lang in Settings::default().supported_languages {
+            let template = templates.get(&(notification_type.clone(), lang.clone()))
+                .unwrap_or_else(|| panic!("{:?} has no {} template", notification_type, lang));
+            assert_eq!(template.matches("{}").count(), english.matches("{}").count(), "{:?} in {}", notification_type, lang);
+        }
+    }
+}
+
+#[test]
+fn added_notifications_keep_their_text() {
+    register("alice");
+    add_notification("alice".to_string(), "Bins are collected on Friday".to_string(), NotificationType::AchievementShared).unwrap();
+
+    let notifications = get_notifications("alice".to_string(), 0, 10, false);
+    assert_eq!(notifications[0].message, "Bins are collected on Friday");
+}
+
+#[test]
+fn sent_notifications_render_in_the_recipients_language() {
+    register("alice");
+    register_user("bob".to_string(), "Bob Example".to_string(), "bob@example.com".to_string(), "password".to_string(), None, "fr".to_string(), None)
+        .unwrap();
+    send_notification("alice".to_string(), "bob".to_string(), "Bravo".to_string()).unwrap();
+
+    let notifications = get_notifications("bob".to_string(), 0, 10, false);
+    assert_eq!(notifications[0].message, "alice a partagé avec vous : Bravo");
+}
+
+// Course completions and deleting users (synth-348)
+
+fn completed_course(user_id: &str, title: &str) {
+    enroll_course(user_id.to_string(), title.to_string()).unwrap();
+    complete_course(user_id.to_string(), title.to_string()).unwrap();
+}
+
+#[test]
+fn completion_count_follows_completions_and_deletions() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    add_course("admin".to_string(), "Recycling 101".to_string(), HashMap::new(), vec![], vec![], 0).unwrap();
+
+    completed_course("alice", "Recycling 101");
+    completed_course("bob", "Recycling 101");
+    assert_eq!(get_course_completion_count("Recycling 101".to_string()), 2);
+
+    delete_user("admin".to_string(), "alice".to_string()).unwrap();
+    assert_eq!(get_course_completion_count("Recycling 101".to_string()), 1);
+}
+
+#[test]
+fn deleting_a_user_removes_them_from_challenges_ratings_and_referrals() {
+    claim_admin("admin");
+    register("alice");
+    register_user("bob".to_string(), "Bob Example".to_string(), "bob@example.com".to_string(), "password".to_string(), None, "en".to_string(), Some("alice".to_string()))
+        .unwrap();
+    add_course("admin".to_string(), "Recycling 101".to_string(), HashMap::new(), vec![], vec![], 0).unwrap();
+    rate_course("alice".to_string(), "Recycling 101".to_string(), 5).unwrap();
+    add_challenge_as("admin", 10).unwrap();
+    let challenge_id = restore_storage().challenges.keys().next().cloned().unwrap();
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+
+    delete_user("admin".to_string(), "alice".to_string()).unwrap();
+
+    let state = restore_storage();
+    assert!(state.challenges[&challenge_id].participants.is_empty());
+    assert!(!state.course_ratings.contains_key("Recycling 101"));
+    assert!(state.referrals.is_empty());
+    assert!(!state.token_history.contains_key("alice"));
+}
+
+#[test]
+fn a_deleted_owners_team_passes_to_a_member() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    register("carol");
+    let (shared, _) = team_of("alice", &["bob"]);
+    accept_team_invite("bob".to_string(), login("bob", "web"), shared.clone()).unwrap();
+    let (solo, _) = team_of("alice", &[]);
+    let (other, _) = team_of("carol", &[]);
+
+    delete_user("admin".to_string(), "alice".to_string()).unwrap();
+
+    assert_eq!(team(&shared).owner, "bob");
+    assert_eq!(team(&shared).members, vec!["bob".to_string()]);
+    assert!(get_team(solo).is_none());
+    assert_eq!(team(&other).owner, "carol");
+}
+
+// Notification polling and purging (synth-344)
+
+fn notify(user_id: &str, message: &str) {
+    add_notification(user_id.to_string(), message.to_string(), NotificationType::Announcement).unwrap();
+}
+
+#[test]
+fn notifications_since_a_timestamp_are_newer_ones_oldest_first() {
+    register("alice");
+    for message in ["first", "second", "third"] {
+        notify("alice", message);
+        env::advance_secs(60);
+    }
+
+    let all = get_notifications_since("alice".to_string(), 0);
+    let newer = get_notifications_since("alice".to_string(), all[0].timestamp);
+
+    let messages: Vec<&str> = newer.iter().map(|notification| notification.message.as_str()).collect();
+    assert_eq!(messages, vec!["second", "third"]);
+}
+
+#[test]
+fn purging_removes_only_data_older_than_the_cutoff() {
+    claim_admin("admin");
+    register("alice");
+    notify("alice", "old");
+    env::advance_secs(2 * 24 * 60 * 60);
+    notify("alice", "new");
+
+    let report = purge_old_data("admin".to_string(), 24 * 60 * 60).unwrap();
+
+    assert_eq!(report.notifications, 1);
+    assert_eq!(get_notifications_since("alice".to_string(), 0)[0].message, "new");
+}
+
+#[test]
+fn purging_with_a_huge_age_keeps_everything() {
+    claim_admin("admin");
+    register("alice");
+    notify("alice", "kept");
+
+    let report = purge_old_data("admin".to_string(), u64::MAX).unwrap();
+
+    assert_eq!(report.notifications, 0);
+    assert_eq!(get_notifications_since("alice".to_string(), 0).len(), 1);
+}
+
+// Notification backlog cap (synth-385)
+
+#[test]
+fn backlog_cap_outside_its_range_is_rejected() {
+    claim_admin("admin");
+
+    for cap in [0, MAX_NOTIFICATIONS_CAP + 1] {
+        let result = set_max_notifications("admin".to_string(), cap);
+        assert!(matches!(result, Err(AppError::InvalidSetting(_))));
+    }
+    assert_eq!(restore_storage().settings.max_notifications, DEFAULT_MAX_NOTIFICATIONS);
+}
+
+#[test]
+fn backlog_cap_drops_the_oldest_notifications() {
+    claim_admin("admin");
+    register("alice");
+    set_max_notifications("admin".to_string(), 2).unwrap();
+
+    for message in ["first", "second", "third"] {
+        notify("alice", message);
+    }
+
+    let messages: Vec<String> = get_notifications("alice".to_string(), 0, 10, false).into_iter().map(|notification| notification.message).collect();
+    assert_eq!(messages, vec!["third".to_string(), "second".to_string()]);
+}
+
+#[test]
+fn sending_messages_does_not_grow_the_senders_record() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    set_max_notifications("admin".to_string(), 2).unwrap();
+
+    for _ in 0..5 {
+        send_notification("alice".to_string(), "bob".to_string(), "Hi".to_string()).unwrap();
+    }
+
+    assert!(restore_storage().users.get("alice").unwrap().notifications.is_empty());
+    assert_eq!(get_notifications("bob".to_string(), 0, 10, false).len(), 2);
+}
+
+// Unique emails (synth-365)
+
+#[test]
+fn registering_a_taken_email_in_any_case_is_rejected() {
+    register("alice");
+
+    let result = register_user("bob".to_string(), "Bob Example".to_string(), " ALICE@Example.com".to_string(), "password".to_string(), None, "en".to_string(), None);
+
+    assert!(matches!(result, Err(AppError::EmailAlreadyExists)));
+}
+
+#[test]
+fn changing_to_another_users_email_is_rejected() {
+    register("alice");
+    register("bob");
+    let token = login("bob", "web");
+
+    let result = update_user("bob".to_string(), token, "Bob Example".to_string(), "Alice@Example.com".to_string());
+
+    assert!(matches!(result, Err(AppError::EmailAlreadyExists)));
+    assert_eq!(restore_storage().email_index["bob@example.com"], "bob");
+}
+
+#[test]
+fn changing_an_email_moves_its_index_entry() {
+    register("alice");
+    let token = login("alice", "web");
+
+    update_user("alice".to_string(), token.clone(), "Alice Example".to_string(), "ALICE@example.com".to_string()).unwrap();
+    assert_eq!(restore_storage().email_index["alice@example.com"], "alice");
+
+    update_user("alice".to_string(), token, "Alice Example".to_string(), "alice@example.org".to_string()).unwrap();
+    let index = restore_storage().email_index;
+    assert_eq!(index["alice@example.org"], "alice");
+    assert!(!index.contains_key("alice@example.com"));
+    register_user("bob".to_string(), "Bob Example".to_string(), "alice@example.com".to_string(), "password".to_string(), None, "en".to_string(), None)
+        .unwrap();
+}
+
+// Quizzes and challenges for logged-in users (synth-389)
+
+#[test]
+fn pass_quiz_requires_a_live_session() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz_as("admin", "Glass", 10).unwrap();
+
+    let result = pass_quiz("alice".to_string(), "forged".to_string(), "Glass".to_string(), vec!["Yes".to_string()]);
+
+    assert!(matches!(result, Err(AppError::SessionTokenExpired)));
+    assert_eq!(quiz_attempts("alice", "Glass"), 0);
+}
+
+#[test]
+fn pass_quiz_credits_the_reward_once_for_passing_answers() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz_as("admin", "Glass", 10).unwrap();
+    let token = login("alice", "web");
+    let before = tokens_of("alice");
+
+    let failed = pass_quiz("alice".to_string(), token.clone(), "Glass".to_string(), vec!["No".to_string()]).unwrap();
+    assert!(!failed.passed);
+    assert_eq!(tokens_of("alice"), before);
+
+    let passed = pass_quiz("alice".to_string(), token.clone(), "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    assert!(passed.passed);
+    assert_eq!(tokens_of("alice"), before + 10);
+
+    let again = pass_quiz("alice".to_string(), token, "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    assert!(again.already_passed);
+    assert_eq!(tokens_of("alice"), before + 10);
+}
+
+#[test]
+fn participating_in_a_challenge_pays_its_reward_once() {
+    claim_admin("admin");
+    register("alice");
+    add_challenge_as("admin", 25).unwrap();
+    let challenge_id = restore_storage().challenges.keys().next().cloned().unwrap();
+    let before = tokens_of("alice");
+
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+
+    assert_eq!(tokens_of("alice"), before + 25);
+    assert!(participate_in_challenge("alice".to_string(), challenge_id.clone()).is_err());
+    assert_eq!(tokens_of("alice"), before + 25);
+    assert_eq!(restore_storage().challenges[&challenge_id].participants, vec!["alice".to_string()]);
+}
+
+// Quiz pass records (synth-380)
+
+#[test]
+fn a_pass_record_keeps_the_score_and_time() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz_as("admin", "Glass", 10).unwrap();
+
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["No".to_string()]).unwrap();
+    assert!(get_my_quiz_results("alice".to_string()).is_empty());
+
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    let results = get_my_quiz_results("alice".to_string());
+    assert_eq!(results.len(), 1);
+    assert_eq!((results[0].quiz_title.as_str(), results[0].score, results[0].total), ("Glass", 1, 1));
+    assert_eq!(results[0].passed_at, now());
+    assert_eq!(restore_storage().users.get("alice").unwrap().passed_quizzes, vec!["Glass".to_string()]);
+}
+
+// Batched notification writes (synth-381)
+
+// Every byte of stable memory, under all of the memory manager's regions
+fn stable_bytes() -> Vec<u8> {
+    use ic_stable_structures::Memory as _;
+    let memory = stable_memory();
+    let mut bytes = vec![0; (memory.size() * 65_536) as usize];
+    memory.read(0, &mut bytes);
+    bytes
+}
+
+#[test]
+fn a_broadcast_saves_once_whatever_the_number_of_recipients() {
+    claim_admin("admin");
+    for index in 0..50 {
+        register(&format!("user{}", index));
+    }
+
+    let before = saves();
+    assert_eq!(broadcast_notification("admin".to_string(), "Bins are collected on Friday".to_string()).unwrap(), 51);
+
+    // One save for the notifications and one for the log entry
+    assert_eq!(saves() - before, 2);
+    assert_eq!(get_notifications("user49".to_string(), 0, 10, false).len(), 1);
+}
+
+#[test]
+fn challenge_reminders_save_once_whatever_the_number_of_recipients() {
+    claim_admin("admin");
+    for index in 0..20 {
+        register(&format!("user{}", index));
+    }
+    add_challenge("admin".to_string(), "Collect 10 bottles".to_string(), 10, vec![], vec![], false, None, "Plastic".to_string(), Some(now() + 60))
+        .unwrap();
+
+    let before = saves();
+    assert_eq!(send_challenge_reminders("admin".to_string()).unwrap(), 21);
+
+    assert_eq!(saves() - before, 2);
+}
+
+#[test]
+fn a_failed_update_leaves_stable_memory_untouched() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz("admin".to_string(), "Glass".to_string(), 1, vec!["Is glass recyclable?".to_string()], vec![vec!["Yes".to_string(), "No".to_string()]], vec!["Yes".to_string()], 10, 0, 100, 60, Difficulty::Easy)
+        .unwrap();
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["No".to_string()]).unwrap();
+
+    let (before, saved) = (stable_bytes(), saves());
+    let result = submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]);
+
+    assert!(matches!(result, Err(AppError::QuizOnCooldown(_))));
+    assert_eq!(saves(), saved);
+    assert!(stable_bytes() == before);
+}
+
+// Timer failures (synth-391)
+
+#[test]
+fn timer_failures_are_written_to_the_action_log() {
+    log_timer_failure("Cycles check", Err::<(), _>(AppError::StorageError("full".to_string())));
+    log_timer_failure("Rank snapshot", Ok::<_, AppError>(()));
+
+    let log = restore_storage().log;
+    assert_eq!(log.len(), 1);
+    assert!(log[0].contains("Cycles check failed"));
+}
+
+// Quiz structure (synth-294)
+
+#[test]
+fn quizzes_with_mismatched_parts_are_rejected() {
+    claim_admin("admin");
+    let add = |questions: usize, options: Vec<Vec<String>>, answers: usize| add_quiz(
+        "admin".to_string(),
+        "Glass".to_string(),
+        1,
+        vec!["Is glass recyclable?".to_string(); questions],
+        options,
+        vec!["Yes".to_string(); answers],
+        10,
+        0,
+        70,
+        0,
+        Difficulty::Easy,
+    );
+    let yes_no = vec!["Yes".to_string(), "No".to_string()];
+
+    assert!(matches!(add(2, vec![yes_no.clone()], 2), Err(AppError::InvalidQuizStructure)));
+    assert!(matches!(add(1, vec![yes_no.clone()], 2), Err(AppError::InvalidQuizStructure)));
+    assert!(matches!(add(1, vec![vec!["Yes".to_string()]], 1), Err(AppError::InvalidQuizStructure)));
+    assert!(add(1, vec![yes_no], 1).is_ok());
+}
+
+// Pass thresholds (synth-337)
+
+// Question i offers "right i" and "wrong i"
+fn graded_quiz(questions: usize, pass_threshold: u8, cooldown_secs: u64, difficulty: Difficulty, reward: u32) -> Quiz {
+    Quiz {
+        level: 1,
+        questions: (0..questions).map(|index| format!("Question {}", index)).collect(),
+        options: (0..questions).map(|index| vec![format!("right {}", index), format!("wrong {}", index)]).collect(),
+        correct_answers: (0..questions).map(|index| format!("right {}", index)).collect(),
+        reward,
+        time_limit_secs: 0,
+        pass_threshold,
+        cooldown_secs,
+        difficulty,
+    }
+}
+
+// Added by "admin", which the test has claimed
+fn add_graded_quiz(title: &str, quiz: Quiz) {
+    add_quiz("admin".to_string(), title.to_string(), quiz.level, quiz.questions, quiz.options, quiz.correct_answers, quiz.reward, quiz.time_limit_secs, quiz.pass_threshold, quiz.cooldown_secs, quiz.difficulty)
+        .unwrap();
+}
+
+// Answers to a graded quiz, right or wrong per question
+fn answers(correct: &[bool]) -> Vec<String> {
+    correct.iter()
+        .enumerate()
+        .map(|(index, right)| format!("{} {}", if *right { "right" } else { "wrong" }, index))
+        .collect()
+}
+
+#[test]
+fn pass_threshold_decides_whether_half_marks_pass() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Lenient", graded_quiz(2, 50, 0, Difficulty::Easy, 10));
+    add_graded_quiz("Strict", graded_quiz(2, 90, 0, Difficulty::Easy, 10));
+
+    assert!(submit_quiz("alice".to_string(), "Lenient".to_string(), answers(&[true, false])).unwrap().passed);
+    assert!(!submit_quiz("alice".to_string(), "Strict".to_string(), answers(&[true, false])).unwrap().passed);
+}
+
+#[test]
+fn editing_a_course_cannot_add_a_zero_threshold_level() {
+    claim_admin("admin");
+    register("alice");
+    course_with_levels("Recycling 101", 1);
+
+    let levels = HashMap::from([(1, graded_quiz(1, 0, 0, Difficulty::Easy, 10))]);
+    let result = edit_course("admin".to_string(), "Recycling 101".to_string(), levels, vec![]);
+
+    assert!(matches!(result, Err(AppError::InvalidPassThreshold)));
+    assert_eq!(restore_storage().courses.get("Recycling 101").unwrap().levels[&1].pass_threshold, 70);
+}
+
+// Partial credit (synth-338)
+
+#[test]
+fn results_mark_each_question_and_count_missing_answers_wrong() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Glass", graded_quiz(3, 70, 0, Difficulty::Easy, 10));
+
+    let result = submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[true, false])).unwrap();
+
+    assert_eq!(result.per_question, vec![true, false, false]);
+    assert_eq!((result.score, result.total, result.passed), (1, 3, false));
+}
+
+// Quiz reward idempotency (synth-341)
+
+#[test]
+fn passing_a_quiz_twice_pays_once() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Glass", graded_quiz(1, 70, 0, Difficulty::Easy, 10));
+    let before = tokens_of("alice");
+
+    submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[true])).unwrap();
+    let again = submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[true])).unwrap();
+
+    assert!(again.passed && again.already_passed);
+    assert_eq!(tokens_of("alice"), before + 10);
+}
+
+// Question shuffling (synth-356)
+
+#[test]
+fn shuffled_answers_are_graded_against_the_canonical_order() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Glass", graded_quiz(6, 100, 0, Difficulty::Easy, 10));
+
+    let session = start_quiz("alice".to_string(), "Glass".to_string(), true).unwrap();
+    assert_ne!(session.questions, graded_quiz(6, 100, 0, Difficulty::Easy, 10).questions);
+    let shown_answers: Vec<String> = session.options.iter()
+        .map(|choices| choices.iter().find(|choice| choice.starts_with("right")).unwrap().clone())
+        .collect();
+    let result = submit_quiz("alice".to_string(), "Glass".to_string(), shown_answers).unwrap();
+
+    assert_eq!((result.score, result.passed), (6, true));
+}
+
+// Quiz cooldowns (synth-367)
+
+#[test]
+fn quiz_cooldown_rejects_early_attempts_and_allows_later_ones() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Glass", graded_quiz(1, 70, 600, Difficulty::Easy, 10));
+    submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[false])).unwrap();
+
+    env::advance_secs(100);
+    let early = submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[true]));
+    assert!(matches!(early, Err(AppError::QuizOnCooldown(500))));
+
+    env::advance_secs(500);
+    assert!(submit_quiz("alice".to_string(), "Glass".to_string(), answers(&[true])).unwrap().passed);
+}
+
+// Quiz difficulty (synth-375)
+
+#[test]
+fn harder_quizzes_pay_more_for_the_same_base_reward() {
+    claim_admin("admin");
+    register("alice");
+    add_graded_quiz("Easy", graded_quiz(1, 70, 0, Difficulty::Easy, 10));
+    add_graded_quiz("Hard", graded_quiz(1, 70, 0, Difficulty::Hard, 10));
+
+    let before = tokens_of("alice");
+    submit_quiz("alice".to_string(), "Easy".to_string(), answers(&[true])).unwrap();
+    let easy = tokens_of("alice") - before;
+    submit_quiz("alice".to_string(), "Hard".to_string(), answers(&[true])).unwrap();
+    let hard = tokens_of("alice") - before - easy;
+
+    assert_eq!((easy, hard), (10, 20));
+}
+
+// Dry-run answer validation (synth-376)
+
+#[test]
+fn dry_run_validation_flags_unknown_options() {
+    claim_admin("admin");
+    add_graded_quiz("Glass", graded_quiz(2, 70, 0, Difficulty::Easy, 10));
+
+    let good = validate_quiz_answers("Glass".to_string(), answers(&[true, false])).unwrap();
+    assert!(good.count_matches);
+    assert_eq!(good.valid_answers, vec![true, true]);
+
+    let bad = validate_quiz_answers("Glass".to_string(), vec!["right 0".to_string(), "maybe".to_string(), "right 2".to_string()]).unwrap();
+    assert_eq!(bad.expected_count, 2);
+    assert!(!bad.count_matches);
+    assert_eq!(bad.valid_answers, vec![true, false, false]);
+}
+
+// Course quiz batches (synth-317)
+
+// A course whose levels are graded quizzes, with alice enrolled
+fn course_with_levels(title: &str, levels: u32) {
+    let levels = (1..=levels).map(|level| (level, Quiz { level, ..graded_quiz(1, 70, 0, Difficulty::Easy, 10) })).collect();
+    add_course("admin".to_string(), title.to_string(), levels, vec![], vec![], 0).unwrap();
+    enroll_course("alice".to_string(), title.to_string()).unwrap();
+}
+
+#[test]
+fn submitting_every_level_completes_the_course() {
+    claim_admin("admin");
+    register("alice");
+    course_with_levels("Recycling 101", 2);
+
+    let result = submit_course_quizzes("alice".to_string(), "Recycling 101".to_string(), HashMap::from([(1, answers(&[true])), (2, answers(&[true]))])).unwrap();
+
+    assert!(result.course_completed);
+    assert!(result.level_results.iter().all(|(_, result)| result.passed));
+    assert_eq!(get_course_completion_count("Recycling 101".to_string()), 1);
+}
+
+#[test]
+fn failing_a_level_leaves_the_course_incomplete() {
+    claim_admin("admin");
+    register("alice");
+    course_with_levels("Recycling 101", 2);
+    let before = tokens_of("alice");
+
+    let result = submit_course_quizzes("alice".to_string(), "Recycling 101".to_string(), HashMap::from([(1, answers(&[true])), (2, answers(&[false]))])).unwrap();
+
+    assert!(!result.course_completed);
+    let passed: Vec<(u32, bool)> = result.level_results.iter().map(|(level, result)| (*level, result.passed)).collect();
+    assert_eq!(passed, vec![(1, true), (2, false)]);
+    assert_eq!(tokens_of("alice"), before + 10);
+}
+
+// Course administration (synth-295)
+
+// A course without levels, added by "admin"
+fn add_plain_course(title: &str, prerequisites: &[&str], token_cost: u32) {
+    let prerequisites = prerequisites.iter().map(|title| title.to_string()).collect();
+    add_course("admin".to_string(), title.to_string(), HashMap::new(), vec!["https://example.com/guide".to_string()], prerequisites, token_cost).unwrap();
+}
+
+#[test]
+fn editing_a_course_replaces_its_levels_and_resources() {
+    claim_admin("admin");
+    add_plain_course("Recycling 101", &[], 0);
+
+    let levels = HashMap::from([(1, graded_quiz(1, 70, 0, Difficulty::Easy, 10))]);
+    edit_course("admin".to_string(), "Recycling 101".to_string(), levels, vec![]).unwrap();
+
+    let course = get_course("Recycling 101".to_string()).unwrap();
+    assert_eq!(course.level_count, 1);
+    assert!(course.educational_resources.is_empty());
+    assert!(matches!(edit_course("admin".to_string(), "Missing".to_string(), HashMap::new(), vec![]), Err(AppError::CourseNotFound)));
+}
+
+#[test]
+fn a_course_required_by_a_challenge_cannot_be_deleted() {
+    claim_admin("admin");
+    add_plain_course("Recycling 101", &[], 0);
+    add_plain_course("Composting", &[], 0);
+    add_challenge("admin".to_string(), "Collect 10 bottles".to_string(), 10, vec!["Recycling 101".to_string()], vec![], false, None, "Plastic".to_string(), None)
+        .unwrap();
+
+    assert!(matches!(delete_course("admin".to_string(), "Recycling 101".to_string()), Err(AppError::CourseInUse)));
+    delete_course("admin".to_string(), "Composting".to_string()).unwrap();
+
+    assert!(get_course("Recycling 101".to_string()).is_ok());
+    assert!(matches!(get_course("Composting".to_string()), Err(AppError::CourseNotFound)));
+}
+
+// Course prerequisites (synth-296)
+
+#[test]
+fn courses_complete_only_once_their_prerequisites_are() {
+    claim_admin("admin");
+    register("alice");
+    add_plain_course("Recycling 101", &[], 0);
+    add_plain_course("Recycling 201", &["Recycling 101"], 0);
+    enroll_course("alice".to_string(), "Recycling 201".to_string()).unwrap();
+
+    let early = complete_course("alice".to_string(), "Recycling 201".to_string());
+    assert!(matches!(early, Err(AppError::RequiredCoursesNotCompleted)));
+
+    completed_course("alice", "Recycling 101");
+    complete_course("alice".to_string(), "Recycling 201".to_string()).unwrap();
+    assert_eq!(get_course_completion_count("Recycling 201".to_string()), 1);
+}
+
+// Course progress (synth-297)
+
+#[test]
+fn course_progress_counts_passed_levels() {
+    claim_admin("admin");
+    register("alice");
+    course_with_levels("Recycling 101", 4);
+    let progress = || get_course_progress("alice".to_string(), "Recycling 101".to_string()).unwrap();
+
+    assert_eq!((progress().passed_levels, progress().percent_complete), (0, 0.0));
+
+    submit_course_quizzes("alice".to_string(), "Recycling 101".to_string(), HashMap::from([(1, answers(&[true]))])).unwrap();
+    assert_eq!((progress().total_levels, progress().passed_levels, progress().percent_complete), (4, 1, 25.0));
+
+    let rest = (2..=4).map(|level| (level, answers(&[true]))).collect();
+    submit_course_quizzes("alice".to_string(), "Recycling 101".to_string(), rest).unwrap();
+    assert_eq!(progress().percent_complete, 100.0);
+}
+
+// Course enrollment (synth-330)
+
+#[test]
+fn completing_a_course_requires_enrolling_first() {
+    claim_admin("admin");
+    register("alice");
+    add_plain_course("Recycling 101", &[], 0);
+
+    let unenrolled = complete_course("alice".to_string(), "Recycling 101".to_string());
+    assert!(matches!(unenrolled, Err(AppError::NotEnrolled)));
+
+    enroll_course("alice".to_string(), "Recycling 101".to_string()).unwrap();
+    assert_eq!(list_enrollments("alice".to_string()), vec!["Recycling 101".to_string()]);
+
+    complete_course("alice".to_string(), "Recycling 101".to_string()).unwrap();
+    assert!(list_enrollments("alice".to_string()).is_empty());
+    assert_eq!(restore_storage().users.get("alice").unwrap().completed_courses, vec!["Recycling 101".to_string()]);
+}
+
+// Course ratings (synth-331)
+
+#[test]
+fn a_new_rating_replaces_the_users_last_one_in_the_average() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    add_plain_course("Recycling 101", &[], 0);
+    assert_eq!(get_course("Recycling 101".to_string()).unwrap().average_rating, None);
+
+    assert_eq!(rate_course("alice".to_string(), "Recycling 101".to_string(), 5).unwrap(), 5.0);
+    assert_eq!(rate_course("bob".to_string(), "Recycling 101".to_string(), 2).unwrap(), 3.5);
+    assert_eq!(rate_course("alice".to_string(), "Recycling 101".to_string(), 4).unwrap(), 3.0);
+    assert_eq!(get_course("Recycling 101".to_string()).unwrap().average_rating, Some(3.0));
+    assert!(matches!(rate_course("alice".to_string(), "Recycling 101".to_string(), 6), Err(AppError::InvalidRating)));
+}
+
+// Educational resources (synth-362)
+
+#[test]
+fn educational_resources_are_listed_per_course() {
+    claim_admin("admin");
+    add_plain_course("Recycling 101", &[], 0);
+    add_course("admin".to_string(), "Composting".to_string(), HashMap::new(), vec![], vec![], 0).unwrap();
+
+    assert_eq!(get_educational_resources("Recycling 101".to_string()).unwrap(), vec!["https://example.com/guide".to_string()]);
+    assert!(get_educational_resources("Composting".to_string()).unwrap().is_empty());
+    assert!(matches!(get_educational_resources("Missing".to_string()), Err(AppError::CourseNotFound)));
+}
+
+// Adding educational resources (synth-363)
+
+#[test]
+fn educational_resources_are_appended_once_and_removed_by_index() {
+    claim_admin("admin");
+    add_plain_course("Recycling 101", &[], 0);
+    let add = |resource: &str| add_educational_resource("admin".to_string(), "Recycling 101".to_string(), resource.to_string());
+
+    add("https://example.com/video").unwrap();
+    assert!(matches!(add("https://example.com/video"), Err(AppError::ResourceAlreadyExists)));
+
+    remove_educational_resource("admin".to_string(), "Recycling 101".to_string(), 0).unwrap();
+    assert_eq!(get_educational_resources("Recycling 101".to_string()).unwrap(), vec!["https://example.com/video".to_string()]);
+    let out_of_range = remove_educational_resource("admin".to_string(), "Recycling 101".to_string(), 1);
+    assert!(matches!(out_of_range, Err(AppError::ResourceNotFound)));
+}
+
+// Course completers (synth-368)
+
+#[test]
+fn course_completers_are_the_users_who_completed_it() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    add_plain_course("Recycling 101", &[], 0);
+    add_plain_course("Composting", &[], 0);
+    completed_course("alice", "Recycling 101");
+
+    let completers = get_course_completers("admin".to_string(), "Recycling 101".to_string(), 0, 10).unwrap();
+    assert_eq!(completers.iter().map(|user| user.id.as_str()).collect::<Vec<_>>(), vec!["alice"]);
+    assert!(get_course_completers("admin".to_string(), "Composting".to_string(), 0, 10).unwrap().is_empty());
+}
+
+// Premium courses (synth-369)
+
+#[test]
+fn premium_courses_are_paid_for_once() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    add_plain_course("Advanced Sorting", &[], 30);
+    set_tokens("alice", 50);
+    set_tokens("bob", 10);
+
+    enroll_course("alice".to_string(), "Advanced Sorting".to_string()).unwrap();
+    enroll_course("alice".to_string(), "Advanced Sorting".to_string()).unwrap();
+    assert_eq!(tokens_of("alice"), 20);
+
+    let result = enroll_course("bob".to_string(), "Advanced Sorting".to_string());
+    assert!(matches!(result, Err(AppError::InsufficientTokens)));
+    assert_eq!(tokens_of("bob"), 10);
+}
+
+// Completion certificates (synth-386)
+
+#[test]
+fn completing_a_course_issues_a_verifiable_certificate() {
+    claim_admin("admin");
+    register("alice");
+    add_plain_course("Recycling 101", &[], 0);
+    completed_course("alice", "Recycling 101");
+
+    let certificates = get_my_certificates("alice".to_string());
+    assert_eq!(certificates.len(), 1);
+    let certificate = &certificates[0];
+    assert_eq!(certificate.course_title, "Recycling 101");
+    let expected = format!("{:x}", Sha256::digest(format!("alice|Recycling 101|{}", certificate.issued_at)));
+    assert_eq!(certificate.hash, expected);
+
+    assert!(verify_certificate(certificate.id.clone(), expected.to_uppercase()));
+    assert!(!verify_certificate(certificate.id.clone(), "0".repeat(64)));
+    assert!(!verify_certificate("missing".to_string(), expected));
+}
+
+// Minting and burning (synth-299)
+
+#[test]
+fn admins_mint_and_burn_without_going_below_zero() {
+    claim_admin("admin");
+    register("alice");
+    set_tokens("alice", 0);
+
+    mint_tokens("admin".to_string(), "alice".to_string(), 40).unwrap();
+    assert_eq!(tokens_of("alice"), 40);
+    assert_eq!(burn_tokens("admin".to_string(), "alice".to_string(), 100).unwrap(), "Burned 40 tokens");
+    assert_eq!(tokens_of("alice"), 0);
+
+    let supply = restore_storage().supply;
+    assert_eq!((supply.total_minted, supply.total_burned), (40, 40));
+    assert!(matches!(mint_tokens("alice".to_string(), "alice".to_string(), 40), Err(AppError::Unauthorized)));
+    assert!(matches!(burn_tokens("alice".to_string(), "alice".to_string(), 40), Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn credits_that_would_overflow_a_balance_are_refused() {
+    claim_admin("admin");
+    register("alice");
+    set_tokens("alice", u32::MAX - 10);
+
+    assert!(matches!(mint_tokens("admin".to_string(), "alice".to_string(), 11), Err(AppError::BalanceOverflow)));
+    assert_eq!(tokens_of("alice"), u32::MAX - 10);
+    assert_eq!(restore_storage().supply.total_minted, 0);
+
+    add_quiz_as("admin", "Glass", 20).unwrap();
+    let result = submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]);
+    assert!(matches!(result, Err(AppError::BalanceOverflow)));
+    assert!(restore_storage().users.get("alice").unwrap().passed_quizzes.is_empty());
+}
+
+#[test]
+fn transfers_to_a_full_balance_are_refused() {
+    linked_sender();
+    set_tokens("bob", u32::MAX);
+
+    let result = transfer_to_bob(30, None, None);
+    assert!(matches!(result, Err(TransferError::GenericError { error_code: 6, .. })));
+    assert_eq!(balance_of("alice"), 100);
+}
+
+#[test]
+fn refund_to_a_full_balance_leaves_the_cash_out_pending() {
+    let id = pending_cash_out();
+    set_tokens("alice", u32::MAX - 39);
+    let result = settle(&id, TransferOutcome::Rejected("InsufficientFunds".to_string()));
+    assert!(matches!(result, Err(AppError::BalanceOverflow)));
+    assert!(restore_storage().pending_cash_outs.contains_key(&id));
+}
+
+// Token history (synth-300)
+
+#[test]
+fn token_history_records_rewards_and_redemptions_with_their_signs() {
+    reward_redeemer();
+    claim_admin("admin");
+    add_quiz_as("admin", "Glass", 10).unwrap();
+
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    redeem_reward("alice".to_string(), "r1".to_string(), None).unwrap();
+
+    let history = get_token_history("alice".to_string(), 0, 2);
+    let entries: Vec<(i64, &str)> = history.iter().map(|txn| (txn.delta, txn.reason.as_str())).collect();
+    assert_eq!(entries, vec![(-10, "Redeemed reward r1"), (10, "Passed quiz Glass")]);
+}
+
+// Email verification (synth-309)
+
+#[test]
+fn rewards_are_redeemed_only_after_verifying_the_email() {
+    reward_redeemer();
+    let mut state = restore_storage();
+    state.users.get_mut("alice").unwrap().email_verified = false;
+    save_storage(state).unwrap();
+    claim_admin("admin");
+
+    let unverified = redeem_reward("alice".to_string(), "r1".to_string(), None);
+    assert!(matches!(unverified, Err(AppError::EmailNotVerified)));
+
+    let token = get_email_verification_token("admin".to_string(), "alice".to_string()).unwrap().unwrap();
+    assert!(matches!(verify_email("alice".to_string(), "forged".to_string()), Err(AppError::InvalidVerificationToken)));
+    verify_email("alice".to_string(), token).unwrap();
+
+    redeem_reward("alice".to_string(), "r1".to_string(), None).unwrap();
+}
+
+// Reward categories (synth-318)
+
+// Rewards costing 10, 50 and 200 tokens, added by "admin"
+fn add_rewards() {
+    add_reward("admin".to_string(), "Tote bag".to_string(), 10, "Merchandise".to_string()).unwrap();
+    add_reward("admin".to_string(), "Water bottle".to_string(), 50, "Merchandise".to_string()).unwrap();
+    add_reward("admin".to_string(), "Tree planted".to_string(), 200, "Donation".to_string()).unwrap();
+}
+
+#[test]
+fn rewards_are_filtered_by_category() {
+    claim_admin("admin");
+    add_rewards();
+
+    let merchandise: Vec<String> = list_rewards_by_category("Merchandise".to_string()).into_iter().map(|reward| reward.description).collect();
+    assert_eq!(merchandise.len(), 2);
+    assert!(merchandise.contains(&"Tote bag".to_string()) && merchandise.contains(&"Water bottle".to_string()));
+
+    let mut categories = list_reward_categories();
+    categories.sort();
+    assert_eq!(categories, vec!["Donation".to_string(), "Merchandise".to_string()]);
+}
+
+// Reward affordability (synth-319)
+
+#[test]
+fn reward_listing_flags_what_the_user_can_afford() {
+    claim_admin("admin");
+    register("alice");
+    add_rewards();
+    set_tokens("alice", 50);
+
+    let mut listing: Vec<(u32, bool)> = list_rewards_for_user("alice".to_string()).unwrap().into_iter()
+        .map(|entry| (entry.reward.cost_tokens, entry.can_afford))
+        .collect();
+    listing.sort();
+    assert_eq!(listing, vec![(10, true), (50, true), (200, false)]);
+}
+
+// Reputation tiers (synth-320)
+
+#[test]
+fn reputation_tier_changes_exactly_at_each_threshold() {
+    register("alice");
+    let tier_at = |tokens: u32| {
+        set_tokens("alice", tokens);
+        get_reputation("alice".to_string()).unwrap()
+    };
+
+    assert_eq!(tier_at(99).tier, Tier::Bronze);
+    assert_eq!(tier_at(99).tokens_to_next_tier, 1);
+    assert_eq!(tier_at(100).tier, Tier::Silver);
+    assert_eq!(tier_at(499).tier, Tier::Silver);
+    assert_eq!(tier_at(500).tier, Tier::Gold);
+    assert_eq!(tier_at(999).tier, Tier::Gold);
+    let top = tier_at(1_000);
+    assert_eq!((top.tier, top.next_tier, top.tokens_to_next_tier), (Tier::Platinum, None, 0));
+}
+
+// Referrals (synth-321)
+
+#[test]
+fn referrers_are_credited_and_self_referral_is_rejected() {
+    register("alice");
+    let before = tokens_of("alice");
+    let register_referred = |id: &str, referrer: &str| register_user(id.to_string(), format!("{} Example", id), format!("{}@example.com", id), "password".to_string(), None, "en".to_string(), Some(referrer.to_string()));
+
+    register_referred("bob", "alice").unwrap();
+    assert_eq!(tokens_of("alice"), before + Settings::default().referral_bonus);
+    assert_eq!(get_referrals("alice".to_string()), vec!["bob".to_string()]);
+
+    assert!(matches!(register_referred("carol", "carol"), Err(AppError::SelfReferral)));
+    assert!(matches!(register_referred("dave", "nobody"), Err(AppError::UserNotFound)));
+}
+
+// Signup and profile bonuses (synth-349)
+
+#[test]
+fn signup_bonus_lands_and_the_profile_bonus_is_paid_once() {
+    claim_admin("admin");
+    register("alice");
+    assert_eq!(tokens_of("alice"), Settings::default().signup_bonus);
+    add_plain_course("Recycling 101", &[], 0);
+    add_plain_course("Composting", &[], 0);
+
+    completed_course("alice", "Recycling 101");
+    completed_course("alice", "Composting");
+
+    let history = get_token_history("alice".to_string(), 0, 100);
+    let bonuses = history.iter().filter(|txn| txn.reason == "Profile completion bonus").count();
+    assert_eq!(bonuses, 1);
+    assert_eq!(tokens_of("alice"), Settings::default().signup_bonus + Settings::default().profile_completion_bonus);
+}
+
+// Reward multiplier (synth-343)
+
+#[test]
+fn quiz_rewards_are_multiplied_only_inside_the_window() {
+    claim_admin("admin");
+    register("alice");
+    add_quiz_as("admin", "Before", 10).unwrap();
+    add_quiz_as("admin", "During", 10).unwrap();
+    set_reward_multiplier("admin".to_string(), 2.0, now() + 60, now() + 120).unwrap();
+
+    let before = tokens_of("alice");
+    submit_quiz("alice".to_string(), "Before".to_string(), vec!["Yes".to_string()]).unwrap();
+    assert_eq!(tokens_of("alice"), before + 10);
+
+    env::advance_secs(60);
+    submit_quiz("alice".to_string(), "During".to_string(), vec!["Yes".to_string()]).unwrap();
+    assert_eq!(tokens_of("alice"), before + 30);
+}
+
+// Nearest reward (synth-359)
+
+#[test]
+fn nearest_reward_is_the_cheapest_one_out_of_reach() {
+    claim_admin("admin");
+    register("alice");
+    add_rewards();
+
+    set_tokens("alice", 30);
+    let nearest = get_nearest_affordable_reward("alice".to_string()).unwrap();
+    assert_eq!(nearest.reward.unwrap().cost_tokens, 50);
+    assert_eq!((nearest.tokens_needed, nearest.all_affordable), (20, false));
+
+    set_tokens("alice", 500);
+    let nearest = get_nearest_affordable_reward("alice".to_string()).unwrap();
+    assert!(nearest.reward.is_none() && nearest.all_affordable);
+}
+
+// Redemption notifications (synth-370)
+
+#[test]
+fn redeeming_a_reward_notifies_the_user() {
+    reward_redeemer();
+
+    redeem_reward("alice".to_string(), "r1".to_string(), None).unwrap();
+
+    let notifications = get_notifications("alice".to_string(), 0, 10, false);
+    assert!(notifications[0].notification_type == NotificationType::RewardRedeemed);
+    assert_eq!(notifications[0].message, "You redeemed Tote bag. Remaining balance: 90 tokens");
+}
+
+// Activity feed (synth-371)
+
+#[test]
+fn activity_feed_lists_quiz_passes_and_redemptions_newest_first() {
+    reward_redeemer();
+    claim_admin("admin");
+    add_quiz_as("admin", "Glass", 10).unwrap();
+
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    redeem_reward("alice".to_string(), "r1".to_string(), None).unwrap();
+
+    let feed = get_activity_feed("alice".to_string(), 0, 10);
+    assert_eq!(feed.len(), 2);
+    assert!(matches!(feed[0].kind, ActivityKind::RewardRedeemed) && feed[0].subject == "r1");
+    assert!(matches!(feed[1].kind, ActivityKind::QuizPassed) && feed[1].subject == "Glass");
+}
+
+// Transfer limits (synth-373)
+
+#[test]
+fn transfers_beyond_the_daily_cap_are_rejected_until_the_next_day() {
+    linked_sender();
+    claim_admin("admin");
+    set_transfer_limits("admin".to_string(), 30, 50).unwrap();
+    env::set_caller(principal_of("alice"));
+
+    assert!(matches!(transfer_to_bob(31, None, None), Err(TransferError::GenericError { error_code: 4, .. })));
+    transfer_to_bob(30, None, None).unwrap();
+    assert!(matches!(transfer_to_bob(30, None, None), Err(TransferError::GenericError { error_code: 4, .. })));
+    transfer_to_bob(20, None, None).unwrap();
+
+    env::advance_secs(24 * 60 * 60);
+    transfer_to_bob(30, None, None).unwrap();
+    assert_eq!(balance_of("alice"), 20);
+}
+
+// Setting balances (synth-379)
+
+#[test]
+fn setting_a_balance_records_the_difference() {
+    claim_admin("admin");
+    register("alice");
+    set_tokens("alice", 50);
+
+    set_user_tokens("admin".to_string(), "alice".to_string(), 80).unwrap();
+    set_user_tokens("admin".to_string(), "alice".to_string(), 20).unwrap();
+
+    assert_eq!(tokens_of("alice"), 20);
+    let deltas: Vec<i64> = get_token_history("alice".to_string(), 0, 2).iter().map(|txn| txn.delta).collect();
+    assert_eq!(deltas, vec![-60, 30]);
+    let supply = restore_storage().supply;
+    assert_eq!((supply.total_minted, supply.total_burned), (30, 60));
+}
+
+// Recurring challenges (synth-305)
+
+// Added by "admin", which the test has claimed; returns the new challenge's id
+fn add_challenge_with(description: &str, required_courses: &[&str], recurrence_secs: Option<u64>, category: &str, ends_at: Option<i64>) -> String {
+    let required_courses = required_courses.iter().map(|title| title.to_string()).collect();
+    add_challenge("admin".to_string(), description.to_string(), 10, required_courses, vec![], false, recurrence_secs, category.to_string(), ends_at)
+        .unwrap();
+    restore_storage().challenges.iter()
+        .find(|(_, challenge)| challenge.description == description)
+        .map(|(id, _)| id.clone())
+        .unwrap()
+}
+
+#[test]
+fn recurring_challenges_reopen_after_their_interval() {
+    claim_admin("admin");
+    register("alice");
+    let challenge_id = add_challenge_with("Weekly litter pick", &[], Some(7 * 24 * 60 * 60), "Litter", None);
+    let before = tokens_of("alice");
+
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+    env::advance_secs(6 * 24 * 60 * 60);
+    let early = participate_in_challenge("alice".to_string(), challenge_id.clone());
+    assert!(matches!(early, Err(AppError::ChallengeNotAvailableYet)));
+
+    env::advance_secs(24 * 60 * 60);
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+    assert_eq!(tokens_of("alice"), before + 20);
+    assert_eq!(restore_storage().users.get("alice").unwrap().challenges_completed, vec![challenge_id]);
+}
+
+// Challenge categories (synth-306)
+
+#[test]
+fn challenges_are_filtered_by_category() {
+    claim_admin("admin");
+    add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    add_challenge_with("Avoid plastic bags", &[], None, "Plastic", None);
+    add_challenge_with("Return an old phone", &[], None, "E-waste", None);
+
+    assert_eq!(list_challenges_by_category("Plastic".to_string()).len(), 2);
+    assert_eq!(list_challenges_by_category("E-waste".to_string())[0].description, "Return an old phone");
+    assert!(list_challenges_by_category("Composting".to_string()).is_empty());
+    assert_eq!(list_categories(), vec!["E-waste".to_string(), "Plastic".to_string()]);
+}
+
+// Challenge leaderboard (synth-307)
+
+#[test]
+fn challenge_leaderboard_ranks_its_participants_by_tokens() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    register("carol");
+    let populated = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    let empty = add_challenge_with("Avoid plastic bags", &[], None, "Plastic", None);
+    participate_in_challenge("alice".to_string(), populated.clone()).unwrap();
+    participate_in_challenge("bob".to_string(), populated.clone()).unwrap();
+    set_tokens("alice", 5);
+    set_tokens("bob", 50);
+
+    let leaderboard = get_challenge_leaderboard(populated).unwrap();
+    assert_eq!(leaderboard, vec![("bob".to_string(), 50), ("alice".to_string(), 5)]);
+    assert!(get_challenge_leaderboard(empty).unwrap().is_empty());
+}
+
+// Challenge reminders (synth-325)
+
+#[test]
+fn only_non_participants_of_challenges_near_their_deadline_are_reminded() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    let soon = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", Some(now() + 60 * 60));
+    add_challenge_with("Avoid plastic bags", &[], None, "Plastic", Some(now() + 7 * 24 * 60 * 60));
+    participate_in_challenge("alice".to_string(), soon.clone()).unwrap();
+
+    assert_eq!(send_challenge_reminders("admin".to_string()).unwrap(), 2);
+    assert_eq!(send_challenge_reminders("admin".to_string()).unwrap(), 0);
+
+    let reminded = |user_id: &str| get_notifications(user_id.to_string(), 0, 10, false).iter()
+        .any(|notification| notification.notification_type == NotificationType::ChallengeReminder);
+    assert!(reminded("bob") && reminded("admin"));
+    assert!(!reminded("alice"));
+}
+
+#[test]
+fn challenges_past_their_deadline_cannot_be_joined() {
+    claim_admin("admin");
+    register("alice");
+    let challenge_id = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", Some(now() + 60));
+    let before = tokens_of("alice");
+
+    env::advance_secs(60);
+    let result = participate_in_challenge("alice".to_string(), challenge_id.clone());
+
+    assert!(matches!(result, Err(AppError::ChallengeEnded)));
+    assert!(matches!(admin_participate_in_challenge("admin".to_string(), challenge_id, true), Err(AppError::ChallengeEnded)));
+    assert_eq!(tokens_of("alice"), before);
+}
+
+// Challenge participants (synth-336)
+
+#[test]
+fn challenge_participants_are_listed_with_paging() {
+    claim_admin("admin");
+    for id in ["alice", "bob", "carol"] {
+        register(id);
+    }
+    let populated = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    let empty = add_challenge_with("Avoid plastic bags", &[], None, "Plastic", None);
+    for id in ["alice", "bob", "carol"] {
+        participate_in_challenge(id.to_string(), populated.clone()).unwrap();
+    }
+
+    let ids = |offset, limit| get_challenge_participants(populated.clone(), offset, limit).unwrap().into_iter().map(|user| user.id).collect::<Vec<_>>();
+    assert_eq!(ids(0, 10), vec!["alice".to_string(), "bob".to_string(), "carol".to_string()]);
+    assert_eq!(ids(1, 1), vec!["bob".to_string()]);
+    assert!(get_challenge_participants(empty, 0, 10).unwrap().is_empty());
+}
+
+// Challenge reward idempotency (synth-342)
+
+#[test]
+fn participating_twice_pays_the_reward_once() {
+    claim_admin("admin");
+    register("alice");
+    let challenge_id = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    let before = tokens_of("alice");
+
+    participate_in_challenge("alice".to_string(), challenge_id.clone()).unwrap();
+    let again = participate_in_challenge("alice".to_string(), challenge_id);
+
+    assert!(matches!(again, Err(AppError::AlreadyParticipated)));
+    assert_eq!(tokens_of("alice"), before + 10);
+}
+
+// Eligible users (synth-358)
+
+#[test]
+fn eligible_users_are_those_who_completed_the_required_courses() {
+    claim_admin("admin");
+    for id in ["alice", "bob", "carol"] {
+        register(id);
+    }
+    add_plain_course("Recycling 101", &[], 0);
+    add_plain_course("Composting", &[], 0);
+    let challenge_id = add_challenge_with("Start a compost heap", &["Recycling 101", "Composting"], None, "Composting", None);
+    completed_course("alice", "Recycling 101");
+    completed_course("alice", "Composting");
+    completed_course("bob", "Recycling 101");
+
+    assert_eq!(get_eligible_user_count(challenge_id).unwrap(), 1);
+}
+
+// Editing challenges (synth-361)
+
+#[test]
+fn editing_a_challenge_changes_only_the_given_fields() {
+    claim_admin("admin");
+    let challenge_id = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+
+    edit_challenge("admin".to_string(), challenge_id.clone(), None, Some(15), None, None).unwrap();
+    edit_challenge("admin".to_string(), challenge_id.clone(), Some("Collect 20 bottles".to_string()), None, None, None).unwrap();
+
+    let challenge = &restore_storage().challenges[&challenge_id];
+    assert_eq!((challenge.description.as_str(), challenge.reward_tokens), ("Collect 20 bottles", 15));
+    assert_eq!(challenge.category, "Plastic");
+    assert!(challenge.required_courses.is_empty());
+}
+
+// A user's challenges (synth-366)
+
+#[test]
+fn my_challenges_lists_the_ones_taken_part_in() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    let bottles = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    let bags = add_challenge_with("Avoid plastic bags", &[], None, "Plastic", None);
+    participate_in_challenge("alice".to_string(), bottles.clone()).unwrap();
+    participate_in_challenge("alice".to_string(), bags.clone()).unwrap();
+
+    let mine: Vec<String> = get_my_challenges("alice".to_string()).unwrap().into_iter().map(|challenge| challenge.id).collect();
+    assert_eq!(mine, vec![bottles, bags]);
+    assert!(get_my_challenges("bob".to_string()).unwrap().is_empty());
+}
+
+// Admin prerequisite bypass (synth-372)
+
+#[test]
+fn only_admins_may_bypass_challenge_prerequisites() {
+    claim_admin("admin");
+    register("alice");
+    add_plain_course("Recycling 101", &[], 0);
+    let challenge_id = add_challenge_with("Collect 10 bottles", &["Recycling 101"], None, "Plastic", None);
+
+    let user_bypass = admin_participate_in_challenge("alice".to_string(), challenge_id.clone(), true);
+    assert!(matches!(user_bypass, Err(AppError::RequiredCoursesNotCompleted)));
+
+    admin_participate_in_challenge("admin".to_string(), challenge_id.clone(), true).unwrap();
+    assert_eq!(restore_storage().challenges[&challenge_id].participants, vec!["admin".to_string()]);
+}
+
+// Challenge milestones (synth-382)
+
+#[test]
+fn a_challenge_milestone_is_awarded_once_when_crossed() {
+    claim_admin("admin");
+    register("alice");
+    set_challenge_milestones("admin".to_string(), vec![5], 50).unwrap();
+    let ids: Vec<String> = (0..6).map(|index| add_challenge_with(&format!("Challenge {}", index), &[], None, "Plastic", None)).collect();
+
+    for id in &ids[..4] {
+        participate_in_challenge("alice".to_string(), id.clone()).unwrap();
+    }
+    assert!(restore_storage().users.get("alice").unwrap().achievements.is_empty());
+
+    participate_in_challenge("alice".to_string(), ids[4].clone()).unwrap();
+    participate_in_challenge("alice".to_string(), ids[5].clone()).unwrap();
+    assert_eq!(restore_storage().users.get("alice").unwrap().achievements, vec!["Challenge Streak 5".to_string()]);
+    let bonuses = get_token_history("alice".to_string(), 0, 100).iter().filter(|txn| txn.reason == "Reached Challenge Streak 5").count();
+    assert_eq!(bonuses, 1);
+}
+
+// Onboarding checklist (synth-384)
+
+#[test]
+fn onboarding_bonus_is_paid_once_whatever_the_order_of_steps() {
+    claim_admin("admin");
+    register("alice");
+    let challenge_id = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    add_plain_course("Recycling 101", &[], 0);
+    add_quiz_as("admin", "Glass", 10).unwrap();
+
+    participate_in_challenge("alice".to_string(), challenge_id).unwrap();
+    completed_course("alice", "Recycling 101");
+    submit_quiz("alice".to_string(), "Glass".to_string(), vec!["Yes".to_string()]).unwrap();
+    assert!(!get_onboarding_progress("alice".to_string()).unwrap().bonus_awarded);
+
+    let token = get_email_verification_token("admin".to_string(), "alice".to_string()).unwrap().unwrap();
+    verify_email("alice".to_string(), token).unwrap();
+    add_quiz_as("admin", "Paper", 10).unwrap();
+    submit_quiz("alice".to_string(), "Paper".to_string(), vec!["Yes".to_string()]).unwrap();
+
+    assert!(get_onboarding_progress("alice".to_string()).unwrap().bonus_awarded);
+    let bonuses = get_token_history("alice".to_string(), 0, 100).iter().filter(|txn| txn.reason == "Onboarding bonus").count();
+    assert_eq!(bonuses, 1);
+}
+
+// Footprint leaderboard (synth-302)
+
+#[test]
+fn footprint_leaderboard_ranks_latest_scores_and_skips_users_without_any() {
+    for id in ["alice", "bob", "carol", "dave"] {
+        register(id);
+    }
+    record_footprint("alice".to_string(), 10, 9).unwrap();
+    record_footprint("alice".to_string(), 10, 3).unwrap();
+    record_footprint("bob".to_string(), 10, 6).unwrap();
+    record_footprint("carol".to_string(), 10, 8).unwrap();
+
+    let leaderboard = get_footprint_leaderboard(0, 10);
+    assert_eq!(leaderboard, vec![("carol".to_string(), 80.0), ("bob".to_string(), 60.0), ("alice".to_string(), 30.0)]);
+    assert_eq!(get_footprint_leaderboard(1, 1), vec![("bob".to_string(), 60.0)]);
+}
+
+// Footprint goals (synth-303)
+
+#[test]
+fn a_footprint_goal_met_before_its_deadline_is_achieved() {
+    register("alice");
+    set_footprint_goal("alice".to_string(), 75.0, now() + 60).unwrap();
+
+    record_footprint("alice".to_string(), 10, 5).unwrap();
+    let progress = get_goal_progress("alice".to_string()).unwrap();
+    assert_eq!((progress.current_score, progress.achieved), (50.0, false));
+
+    record_footprint("alice".to_string(), 10, 8).unwrap();
+    assert!(get_goal_progress("alice".to_string()).unwrap().achieved);
+    assert_eq!(restore_storage().users.get("alice").unwrap().achievements, vec!["Footprint Goal Reached".to_string()]);
+}
+
+#[test]
+fn a_footprint_goal_met_after_its_deadline_is_missed() {
+    register("alice");
+    set_footprint_goal("alice".to_string(), 75.0, now() + 60).unwrap();
+    env::advance_secs(120);
+
+    record_footprint("alice".to_string(), 10, 8).unwrap();
+
+    let progress = get_goal_progress("alice".to_string()).unwrap();
+    assert!(progress.deadline_passed && !progress.achieved);
+    assert!(restore_storage().users.get("alice").unwrap().achievements.is_empty());
+}
+
+// Global footprint summary (synth-352)
+
+#[test]
+fn global_footprint_summary_totals_each_users_latest_footprint() {
+    register("alice");
+    register("bob");
+    assert_eq!(get_global_footprint_summary().users_counted, 0);
+    record_footprint("alice".to_string(), 100, 100).unwrap();
+    record_footprint("alice".to_string(), 10, 5).unwrap();
+    record_footprint("bob".to_string(), 30, 30).unwrap();
+
+    let summary = get_global_footprint_summary();
+    assert_eq!((summary.total_waste_generated, summary.total_recyclable_waste, summary.users_counted), (40, 35, 2));
+    assert_eq!(summary.average_footprint_score, 75.0);
+}
+
+// Feedback responses (synth-323)
+
+fn feedback_ids() -> Vec<String> {
+    restore_storage().feedbacks.iter().map(|feedback| feedback.id.clone()).collect()
+}
+
+#[test]
+fn responding_to_feedback_notifies_its_author() {
+    claim_admin("admin");
+    register("alice");
+    submit_feedback("alice".to_string(), "The map is slow".to_string(), FeedbackCategory::Bug, None).unwrap();
+
+    respond_to_feedback("admin".to_string(), feedback_ids()[0].clone(), "Fixed, thanks!".to_string(), FeedbackStatus::Resolved).unwrap();
+
+    let feedback = &restore_storage().feedbacks[0];
+    assert_eq!((feedback.response.as_deref(), feedback.status), (Some("Fixed, thanks!"), FeedbackStatus::Resolved));
+    let notifications = get_notifications("alice".to_string(), 0, 10, false);
+    assert_eq!(notifications[0].message, "An admin responded to your feedback: Fixed, thanks!");
+}
+
+// Feedback categories and ratings (synth-324)
+
+#[test]
+fn feedback_stats_count_categories_and_average_ratings() {
+    claim_admin("admin");
+    register("alice");
+    submit_feedback("alice".to_string(), "Crash".to_string(), FeedbackCategory::Bug, Some(2)).unwrap();
+    submit_feedback("alice".to_string(), "Another crash".to_string(), FeedbackCategory::Bug, None).unwrap();
+    submit_feedback("alice".to_string(), "Dark mode".to_string(), FeedbackCategory::Feature, Some(5)).unwrap();
+    let invalid = submit_feedback("alice".to_string(), "Great".to_string(), FeedbackCategory::Other, Some(6));
+    assert!(matches!(invalid, Err(AppError::InvalidRating)));
+
+    let stats = get_feedback_stats("admin".to_string()).unwrap();
+    assert_eq!(stats.counts_by_category, vec![
+        (FeedbackCategory::Bug, 2),
+        (FeedbackCategory::Feature, 1),
+        (FeedbackCategory::Content, 0),
+        (FeedbackCategory::Other, 0),
+    ]);
+    assert_eq!(stats.average_rating, Some(3.5));
+}
+
+// Feedback status (synth-327)
+
+#[test]
+fn feedback_is_listed_by_status() {
+    claim_admin("admin");
+    register("alice");
+    for text in ["First", "Second", "Third"] {
+        submit_feedback("alice".to_string(), text.to_string(), FeedbackCategory::Other, None).unwrap();
+    }
+    respond_to_feedback("admin".to_string(), feedback_ids()[1].clone(), "Done".to_string(), FeedbackStatus::Resolved).unwrap();
+
+    let texts = |status| get_feedbacks_by_status("admin".to_string(), status, 0, 10).unwrap().into_iter().map(|feedback| feedback.feedback).collect::<Vec<_>>();
+    assert_eq!(texts(FeedbackStatus::New), vec!["Third".to_string(), "First".to_string()]);
+    assert_eq!(texts(FeedbackStatus::Resolved), vec!["Second".to_string()]);
+}
+
+// Data export (synth-313)
+
+#[test]
+fn export_round_trips_records_and_redacts_credentials() {
+    claim_admin("admin");
+    register("alice");
+    login("alice", "web");
+    add_plain_course("Recycling 101", &[], 0);
+
+    let first = export_data("admin".to_string(), 0).unwrap();
+    let document: String = (0..first.total_chunks).map(|chunk| export_data("admin".to_string(), chunk).unwrap().data).collect();
+    let export: serde_json::Value = serde_json::from_str(&document).unwrap();
+
+    let alice = export["users"].as_array().unwrap().iter().find(|user| user["id"] == "alice").unwrap();
+    assert_eq!(alice["email"], "alice@example.com");
+    assert_eq!(alice["hashed_password"], "[redacted]");
+    assert_eq!(alice["salt"], "[redacted]");
+    assert!(alice["sessions"].as_array().unwrap().is_empty());
+    assert_eq!(export["courses"][0]["title"], "Recycling 101");
+    assert!(!document.contains(&restore_storage().users.get("alice").unwrap().hashed_password));
+    assert!(matches!(export_data("alice".to_string(), 0), Err(AppError::Unauthorized)));
+}
+
+// Bulk import (synth-314)
+
+#[test]
+fn import_skips_taken_ids_and_emails() {
+    claim_admin("admin");
+    register("alice");
+    let import = |id: &str, email: &str| ImportUser {
+        id: id.to_string(),
+        full_name: format!("{} Example", id),
+        email: email.to_string(),
+        password: "password".to_string(),
+        role: Role::User,
+        preferred_language: "en".to_string(),
+    };
+
+    let report = import_users("admin".to_string(), vec![
+        import("bob", "bob@example.com"),
+        import("alice", "other@example.com"),
+        import("carol", "ALICE@example.com"),
+        import("dave", "bob@example.com"),
+    ]).unwrap();
+
+    assert_eq!(report.created, vec!["bob".to_string()]);
+    assert_eq!(report.skipped, vec!["alice".to_string(), "carol".to_string(), "dave".to_string()]);
+    assert_eq!(get_user_count(), 3);
+    login_user("bob".to_string(), "password".to_string(), "web".to_string()).unwrap();
+}
+
+// Error codes (synth-315)
+
+#[test]
+fn error_codes_are_distinct_and_fixed() {
+    let text = || String::new();
+    let errors = [
+        AppError::UserAlreadyExists, AppError::InvalidCredentials, AppError::UserNotFound, AppError::SessionTokenExpired,
+        AppError::ChallengeNotFound, AppError::CourseAlreadyExists, AppError::QuizAlreadyExists, AppError::RequiredCoursesNotCompleted,
+        AppError::FeedbackError, AppError::StorageError(text()), AppError::NotificationError, AppError::InvalidReward,
+        AppError::QuizNotFound, AppError::MaxAttemptsReached, AppError::QuizNotStarted, AppError::QuizTimedOut,
+        AppError::Unauthorized, AppError::InvalidQuizStructure, AppError::CourseNotFound, AppError::CourseInUse,
+        AppError::GoalNotFound, AppError::TeamNotFound, AppError::ChallengeNotAvailableYet, AppError::InvalidResetToken,
+        AppError::ResetTokenExpired, AppError::EmailNotVerified, AppError::InvalidVerificationToken, AppError::ExportError(text()),
+        AppError::InvalidTierThresholds, AppError::SelfReferral, AppError::FeedbackNotFound, AppError::InvalidRating,
+        AppError::InputTooLong(text()), AppError::NotEnrolled, AppError::AccountSuspended, AppError::LastAdmin,
+        AppError::AdminAlreadyExists, AppError::InvalidPassThreshold, AppError::AlreadyParticipated, AppError::InvalidRewardMultiplier,
+        AppError::InvalidCoordinates, AppError::HttpOutcallError(text()), AppError::LedgerNotConfigured, AppError::InsufficientTokens,
+        AppError::PrincipalNotLinked, AppError::LedgerError(text()), AppError::AchievementNotOwned, AppError::UnsupportedLanguage,
+        AppError::RewardTooLarge, AppError::ResourceAlreadyExists, AppError::ResourceNotFound, AppError::EmailAlreadyExists,
+        AppError::QuizOnCooldown(0), AppError::TransferLimitExceeded, AppError::CertificateNotFound, AppError::ReplayedRequest,
+        AppError::QuizAlreadyStarted, AppError::InvalidSetting(text()), AppError::NotInvited, AppError::CashOutPending(text()),
+        AppError::CashOutNotFound, AppError::BalanceOverflow, AppError::TooManyRequests(0), AppError::ChallengeEnded,
+    ];
+
+    // Codes were assigned in declaration order and must never change
+    let codes: Vec<u32> = errors.iter().map(AppError::code).collect();
+    assert_eq!(codes, (1..=errors.len() as u32).collect::<Vec<_>>());
+}
+
+// Notification paging (synth-316)
+
+#[test]
+fn notifications_page_newest_first_and_filter_unread() {
+    register("alice");
+    assert!(get_notifications("alice".to_string(), 0, 10, false).is_empty());
+    for message in ["first", "second", "third"] {
+        notify("alice", message);
+    }
+    let messages = |offset, limit, only_unread| get_notifications("alice".to_string(), offset, limit, only_unread).into_iter().map(|notification| notification.message).collect::<Vec<_>>();
+
+    assert_eq!(messages(1, 1, false), vec!["second".to_string()]);
+    mark_notifications_read("alice".to_string()).unwrap();
+    notify("alice", "fourth");
+    assert_eq!(messages(0, 10, true), vec!["fourth".to_string()]);
+    assert_eq!(messages(0, 10, false).len(), 4);
+}
+
+// Login streaks (synth-322)
+
+#[test]
+fn login_streak_grows_on_consecutive_days_and_restarts_after_a_gap() {
+    register("alice");
+    let bonus = |streak: u32| (streak * STREAK_BONUS_STEP).min(Settings::default().max_streak_bonus);
+    let before = tokens_of("alice");
+
+    login("alice", "web");
+    login("alice", "web");
+    assert_eq!(get_streak("alice".to_string()), 1);
+    env::advance_secs(24 * 60 * 60);
+    login("alice", "web");
+    assert_eq!(get_streak("alice".to_string()), 2);
+    assert_eq!(tokens_of("alice"), before + bonus(1) + bonus(2));
+
+    env::advance_secs(2 * 24 * 60 * 60);
+    login("alice", "web");
+    assert_eq!(get_streak("alice".to_string()), 1);
+}
+
+// Session sweep (synth-326)
+
+#[test]
+fn sweep_removes_sessions_expired_beyond_the_grace_period() {
+    register("alice");
+    let token = login("alice", "web");
+    env::advance_secs(SESSION_TTL_SECS as u64 + 1);
+    assert_eq!(sweep_expired_sessions().unwrap(), 0);
+
+    env::advance_secs(Settings::default().session_grace_secs as u64);
+    assert_eq!(sweep_expired_sessions().unwrap(), 1);
+    assert_eq!(session_count("alice"), 0);
+    assert!(verify_session("alice".to_string(), token) == SessionStatus::NotFound);
+}
+
+// Input limits (synth-328)
+
+#[test]
+fn over_length_names_are_rejected() {
+    let register_named = |id: &str, full_name: String| register_user(id.to_string(), full_name, format!("{}@example.com", id), "password".to_string(), None, "en".to_string(), None);
+
+    assert!(matches!(register_named("alice", "a".repeat(MAX_NAME_LEN + 1)), Err(AppError::InputTooLong(_))));
+    assert!(!is_registered("alice".to_string()));
+    register_named("bob", "b".repeat(MAX_NAME_LEN)).unwrap();
+}
+
+// System statistics (synth-329)
+
+#[test]
+fn stats_count_each_collection() {
+    claim_admin("admin");
+    register("alice");
+    login("alice", "web");
+    set_tokens("alice", 40);
+    set_tokens("admin", 0);
+    add_plain_course("Recycling 101", &[], 0);
+    add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    submit_feedback("alice".to_string(), "Nice".to_string(), FeedbackCategory::Other, None).unwrap();
+
+    let stats = get_stats("admin".to_string()).unwrap();
+    assert_eq!((stats.total_users, stats.total_courses, stats.total_challenges), (2, 1, 1));
+    assert_eq!((stats.tokens_in_circulation, stats.total_feedback, stats.active_sessions), (40, 1, 1));
+}
+
+// User search (synth-332)
+
+#[test]
+fn users_are_found_by_part_of_their_name_or_email() {
+    claim_admin("admin");
+    register_user("alice".to_string(), "Alice Green".to_string(), "alice@recycle.org".to_string(), "password".to_string(), None, "en".to_string(), None)
+        .unwrap();
+    register("bob");
+    let found = |query: &str| search_users("admin".to_string(), query.to_string(), 0, 10).unwrap().into_iter().map(|user| user.id).collect::<Vec<_>>();
+
+    assert_eq!(found("green"), vec!["alice".to_string()]);
+    assert_eq!(found("RECYCLE.org"), vec!["alice".to_string()]);
+    assert_eq!(found("example.com").len(), 2);
+    assert!(found("nobody").is_empty());
+}
+
+// Suspension (synth-333)
+
+#[test]
+fn suspended_users_cannot_log_in_until_reactivated() {
+    claim_admin("admin");
+    register("alice");
+    let token = login("alice", "web");
+
+    suspend_user("admin".to_string(), "alice".to_string()).unwrap();
+    let result = login_user("alice".to_string(), "password".to_string(), "web".to_string());
+    assert!(matches!(result, Err(AppError::AccountSuspended)));
+    assert!(verify_session("alice".to_string(), token) == SessionStatus::NotFound);
+
+    reactivate_user("admin".to_string(), "alice".to_string()).unwrap();
+    login("alice", "web");
+}
+
+// Role management (synth-334)
+
+#[test]
+fn admins_promote_and_demote_but_keep_one_admin() {
+    claim_admin("admin");
+    register("alice");
+
+    set_user_role("admin".to_string(), "alice".to_string(), Role::Admin).unwrap();
+    set_user_role("admin".to_string(), "alice".to_string(), Role::User).unwrap();
+    assert!(restore_storage().users.get("alice").unwrap().role == Role::User);
+
+    let result = set_user_role("admin".to_string(), "admin".to_string(), Role::User);
+    assert!(matches!(result, Err(AppError::LastAdmin)));
+    assert!(matches!(set_user_role("alice".to_string(), "alice".to_string(), Role::Admin), Err(AppError::Unauthorized)));
+}
+
+#[test]
+fn promoted_admin_is_told_to_link_a_principal_and_then_acts() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+
+    let reply = set_user_role("admin".to_string(), "alice".to_string(), Role::Admin).unwrap();
+    assert_eq!(reply, "User alice is now Admin and must link a principal before using admin endpoints");
+
+    env::set_caller(principal_of("alice"));
+    assert!(matches!(suspend_user("alice".to_string(), "bob".to_string()), Err(AppError::PrincipalNotLinked)));
+    link_principal("alice".to_string(), "password".to_string()).unwrap();
+    suspend_user("alice".to_string(), "bob".to_string()).unwrap();
+    assert!(restore_storage().users.get("bob").unwrap().suspended);
+
+    env::set_caller(principal_of("admin"));
+    assert_eq!(set_user_role("admin".to_string(), "bob".to_string(), Role::User).unwrap(), "User bob is now User");
+}
+
+// Users by role (synth-340)
+
+#[test]
+fn users_are_listed_by_role() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    let ids = |role| get_users_by_role("admin".to_string(), role, 0, 10).unwrap().into_iter().map(|user| user.id).collect::<Vec<_>>();
+
+    assert_eq!(ids(Role::Admin), vec!["admin".to_string()]);
+    assert_eq!(ids(Role::User), vec!["alice".to_string(), "bob".to_string()]);
+}
+
+// First admin (synth-335)
+
+#[test]
+fn only_the_first_admin_can_be_claimed() {
+    claim_admin("admin");
+    assert!(restore_storage().users.get("admin").unwrap().role == Role::Admin);
+
+    env::set_caller(principal_of("mallory"));
+    let second = claim_first_admin("mallory".to_string(), "Mallory".to_string(), "mallory@example.com".to_string(), "password".to_string(), "en".to_string());
+    assert!(matches!(second, Err(AppError::AdminAlreadyExists)));
+    assert!(!is_registered("mallory".to_string()));
+}
+
+// Achievement sharing (synth-351)
+
+#[test]
+fn only_owned_achievements_can_be_shared() {
+    register("alice");
+    register("bob");
+    let mut state = restore_storage();
+    state.users.get_mut("alice").unwrap().achievements.push("Footprint Goal Reached".to_string());
+    save_storage(state).unwrap();
+
+    share_achievement("alice".to_string(), "Footprint Goal Reached".to_string(), "bob".to_string()).unwrap();
+    let notifications = get_notifications("bob".to_string(), 0, 10, false);
+    assert_eq!(notifications[0].message, "alice shared with you: Footprint Goal Reached");
+
+    let unowned = share_achievement("bob".to_string(), "Footprint Goal Reached".to_string(), "alice".to_string());
+    assert!(matches!(unowned, Err(AppError::AchievementNotOwned)));
+}
+
+// Id generation (synth-353)
+
+#[test]
+fn generated_ids_do_not_collide() {
+    let ids: std::collections::HashSet<String> = (0..10_000).map(|_| generate_id()).collect();
+    assert_eq!(ids.len(), 10_000);
+}
+
+// Preferred language (synth-354)
+
+#[test]
+fn only_supported_languages_can_be_chosen() {
+    register("alice");
+
+    set_preferred_language("alice".to_string(), "ES".to_string()).unwrap();
+    assert_eq!(restore_storage().users.get("alice").unwrap().preferred_language, "es");
+    assert!(matches!(set_preferred_language("alice".to_string(), "xx".to_string()), Err(AppError::UnsupportedLanguage)));
+}
+
+// Recommended next action (synth-355)
+
+#[test]
+fn recommended_action_follows_the_onboarding_steps() {
+    claim_admin("admin");
+    register("alice");
+    add_plain_course("Recycling 101", &[], 0);
+    let challenge_id = add_challenge_with("Collect 10 bottles", &[], None, "Plastic", None);
+    let next = || get_next_recommended_action("alice".to_string()).unwrap();
+
+    assert!(matches!(next().kind, ActionKind::VerifyEmail));
+
+    let token = get_email_verification_token("admin".to_string(), "alice".to_string()).unwrap().unwrap();
+    verify_email("alice".to_string(), token).unwrap();
+    assert!(matches!(next().kind, ActionKind::EnrollInCourse));
+    enroll_course("alice".to_string(), "Recycling 101".to_string()).unwrap();
+    assert!(matches!(next().kind, ActionKind::CompleteCourse));
+    complete_course("alice".to_string(), "Recycling 101".to_string()).unwrap();
+    assert!(matches!(next().kind, ActionKind::ParticipateInChallenge));
+    assert_eq!(next().target, Some(challenge_id.clone()));
+
+    participate_in_challenge("alice".to_string(), challenge_id).unwrap();
+    assert!(matches!(next().kind, ActionKind::Done));
+}
+
+// Session checks (synth-364)
+
+#[test]
+fn session_status_tells_valid_expired_and_unknown_tokens_apart() {
+    register("alice");
+    let token = login("alice", "web");
+
+    assert!(verify_session("alice".to_string(), token.clone()) == SessionStatus::Valid);
+    assert!(verify_session("alice".to_string(), "forged".to_string()) == SessionStatus::NotFound);
+    assert!(verify_session("bob".to_string(), token.clone()) == SessionStatus::NotFound);
+
+    env::advance_secs(SESSION_TTL_SECS as u64 + 1);
+    assert!(verify_session("alice".to_string(), token) == SessionStatus::Expired);
+}
+
+// Session grace period (synth-377)
+
+#[test]
+fn expired_sessions_serve_queries_within_the_grace_period_but_not_updates() {
+    register("alice");
+    let token = login("alice", "web");
+    env::advance_secs(SESSION_TTL_SECS as u64 + 60);
+
+    let profile = get_my_profile("alice".to_string(), token.clone()).unwrap();
+    assert!(profile.session_expiring);
+    let update = update_user("alice".to_string(), token.clone(), "Alice Example".to_string(), "alice@example.com".to_string());
+    assert!(matches!(update, Err(AppError::SessionTokenExpired)));
+
+    env::advance_secs(Settings::default().session_grace_secs as u64);
+    assert!(matches!(get_my_profile("alice".to_string(), token), Err(AppError::SessionTokenExpired)));
+}
+
+// Leaderboard around a user (synth-374)
+
+#[test]
+fn leaderboard_around_a_user_is_clipped_at_either_end() {
+    for (id, tokens) in [("alice", 50), ("bob", 40), ("carol", 30), ("dave", 20), ("erin", 10)] {
+        register(id);
+        set_tokens(id, tokens);
+    }
+    let around = |id: &str| get_leaderboard_around_me(id.to_string(), 1).unwrap().into_iter().map(|entry| (entry.rank, entry.user_id)).collect::<Vec<_>>();
+
+    assert_eq!(around("alice"), vec![(1, "alice".to_string()), (2, "bob".to_string())]);
+    assert_eq!(around("carol"), vec![(2, "bob".to_string()), (3, "carol".to_string()), (4, "dave".to_string())]);
+    assert_eq!(around("erin"), vec![(4, "dave".to_string()), (5, "erin".to_string())]);
+}
+
+// User count (synth-378)
+
+#[test]
+fn user_count_and_registration_checks() {
+    assert_eq!(get_user_count(), 0);
+    register("alice");
+
+    assert_eq!(get_user_count(), 1);
+    assert!(is_registered("alice".to_string()));
+    assert!(!is_registered("bob".to_string()));
+}
+
+// Inactive users (synth-383)
+
+#[test]
+fn inactive_users_are_those_not_seen_since_the_cutoff() {
+    claim_admin("admin");
+    register("alice");
+    register("bob");
+    login("alice", "web");
+    env::advance_secs(30 * 24 * 60 * 60);
+    login("bob", "web");
+
+    let inactive: Vec<String> = get_inactive_users("admin".to_string(), 7 * 24 * 60 * 60, 0, 10).unwrap().into_iter().map(|user| user.id).collect();
+    assert_eq!(inactive, vec!["admin".to_string(), "alice".to_string()]);
+}
+
+// Rank changes (synth-387)
+
+#[test]
+fn rank_change_compares_against_the_last_snapshot() {
+    for (id, tokens) in [("alice", 30), ("bob", 20), ("carol", 10)] {
+        register(id);
+        set_tokens(id, tokens);
+    }
+    assert_eq!(get_rank_change("carol".to_string()).unwrap(), 0);
+    snapshot_ranks().unwrap();
+
+    set_tokens("carol", 100);
+
+    assert_eq!(get_rank_change("carol".to_string()).unwrap(), 2);
+    assert_eq!(get_rank_change("alice".to_string()).unwrap(), -1);
+    assert_eq!(get_rank_change("bob".to_string()).unwrap(), -1);
+}
+
+// Storage usage (synth-390)
+
+#[test]
+fn storage_usage_grows_with_the_data() {
+    claim_admin("admin");
+    let before = get_storage_usage("admin".to_string()).unwrap();
+
+    for index in 0..20 {
+        register(&format!("user{}", index));
+    }
+    add_plain_course("Recycling 101", &[], 0);
+    notify("user0", "hello");
+
+    let after = get_storage_usage("admin".to_string()).unwrap();
+    assert!(after.users_bytes > before.users_bytes);
+    assert!(after.courses_bytes > before.courses_bytes);
+    assert!(after.notifications_bytes > before.notifications_bytes);
+    assert!(after.log_bytes > before.log_bytes);
+}
+
+// Low cycles alerts (synth-391)
+
+#[test]
+fn admins_are_alerted_once_while_cycles_stay_low() {
+    claim_admin("admin");
+    register("alice");
+    let threshold = Settings::default().low_cycles_threshold;
+
+    assert_eq!(check_cycles_balance(threshold).unwrap(), 0);
+    assert_eq!(check_cycles_balance(threshold - 1).unwrap(), 1);
+    assert_eq!(check_cycles_balance(threshold - 1).unwrap(), 0);
+
+    let alerts = get_notifications("admin".to_string(), 0, 10, false);
+    assert!(alerts.len() == 1 && alerts[0].notification_type == NotificationType::LowCycles);
+    assert!(get_notifications("alice".to_string(), 0, 10, false).is_empty());
+
+    check_cycles_balance(threshold).unwrap();
+    assert_eq!(check_cycles_balance(threshold - 1).unwrap(), 1);
+}
+
+// Recycling centers (synth-345)
+
+#[test]
+fn transform_keeps_only_the_parsed_centers() {
+    let body = br#"{"version":0.6,"elements":[
+        {"id":2,"lat":51.5,"lon":-0.12,"tags":{"name":"Depot","recycling:glass":"yes","recycling:paper":"no"}},
+        {"id":1,"lat":51.4,"lon":-0.1,"tags":{}},
+        {"id":3,"tags":{"name":"No position"}}
+    ]}"#;
+    let response = transform_recycling_centers(TransformArgs {
+        response: HttpResponse { status: 200u32.into(), headers: vec![HttpHeader { name: "Date".to_string(), value: "now".to_string() }], body: body.to_vec() },
+        context: vec![],
+    });
+
+    assert!(response.headers.is_empty());
+    let centers: Vec<RecyclingCenter> = serde_json::from_slice(&response.body).unwrap();
+    let summary: Vec<(u64, &str, Vec<String>)> = centers.iter().map(|center| (center.id, center.name.as_str(), center.materials.clone())).collect();
+    assert_eq!(summary, vec![(1, "Recycling point", vec![]), (2, "Depot", vec!["glass".to_string()])]);
+}
+
+fn depot(id: u64) -> RecyclingCenter {
+    RecyclingCenter { id, name: "Depot".to_string(), lat: 51.5, lon: -0.12, materials: vec![] }
+}
+
+#[test]
+fn cached_recycling_centers_are_served_to_logged_in_users_without_an_outcall() {
+    register("alice");
+    let token = login("alice", "web");
+    let mut state = restore_storage();
+    state.recycling_centers.insert("51.50,-0.12".to_string(), (now(), vec![depot(7)]));
+    save_storage(state).unwrap();
+    let fetch = |token: &str, lat, lon| poll_once(fetch_recycling_centers("alice".to_string(), token.to_string(), lat, lon));
+
+    assert_eq!(fetch(&token, 51.5012, -0.1249).unwrap()[0].id, 7);
+    assert!(matches!(fetch("forged", 51.5012, -0.1249), Err(AppError::SessionTokenExpired)));
+    assert!(matches!(fetch(&token, 91.0, 0.0), Err(AppError::InvalidCoordinates)));
+}
+
+#[test]
+fn recycling_center_outcalls_are_limited_per_user() {
+    register("alice");
+    let token = login("alice", "web");
+    let mut state = restore_storage();
+    state.users.get_mut("alice").unwrap().last_recycling_fetch = now() - 20;
+    save_storage(state).unwrap();
+
+    let result = poll_once(fetch_recycling_centers("alice".to_string(), token, 48.85, 2.35));
+
+    assert!(matches!(result, Err(AppError::TooManyRequests(40))));
+}
+
+#[test]
+fn recycling_center_cache_drops_expired_and_oldest_areas() {
+    let ttl_secs = Settings::default().recycling_cache_ttl_secs;
+    let mut state = restore_storage();
+    for index in 0..RECYCLING_CACHE_MAX_ENTRIES as i64 {
+        state.recycling_centers.insert(format!("area {}", index), (now() - index, vec![depot(index as u64)]));
+    }
+    state.recycling_centers.insert("stale".to_string(), (now() - ttl_secs as i64, vec![depot(0)]));
+
+    cache_recycling_centers(&mut state.recycling_centers, "51.50,-0.12", vec![depot(7)], ttl_secs);
+
+    let areas: Vec<String> = state.recycling_centers.iter().map(|(area, _)| area).collect();
+    assert_eq!(areas.len(), RECYCLING_CACHE_MAX_ENTRIES);
+    assert!(!areas.contains(&"stale".to_string()));
+    let oldest = format!("area {}", RECYCLING_CACHE_MAX_ENTRIES - 1);
+    assert!(!areas.contains(&oldest) && areas.contains(&"area 0".to_string()));
+    assert_eq!(state.recycling_centers.get("51.50,-0.12").unwrap().1[0].id, 7);
+}